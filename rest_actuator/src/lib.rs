@@ -1,27 +1,52 @@
+// Git commit and compiler captured by build.rs, shared with the crates embedding the
+// actuator so the workspace probes them once
+pub mod build_info {
+    pub const GIT_HASH: &str = env!("GIT_HASH");
+    pub const RUSTC_VERSION: &str = env!("RUSTC_VERSION");
+}
+
 pub mod api {
-    use axum::extract::Extension;
+    use axum::async_trait;
+    use axum::extract::{Extension, MatchedPath, Query, Request, State};
+    use axum::handler::Handler;
+    use axum::middleware::{self, Next};
     use axum::response::IntoResponse;
     use axum::{
         body::Body,
         http::{Response, StatusCode},
-        routing::get,
-        Router,
+        routing::{get, post},
+        Json, Router,
     };
+    use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+    use serde::{Deserialize, Serialize};
     use serde_json::json;
     use std::fmt::Debug;
-    use std::time::Duration;
+    use std::future::Future;
+    use std::path::PathBuf;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
     use std::{
-        collections::HashMap,
-        sync::{Arc, Mutex},
+        collections::{BTreeMap, HashMap, HashSet, VecDeque},
+        sync::{Arc, Mutex, OnceLock},
     };
     use tokio::sync::broadcast;
+    use tokio::sync::broadcast::error::RecvError;
+    use tokio::sync::watch;
+    use tokio::task::JoinHandle;
+
+    // Base period of the background state check loop by default
+    pub const STATE_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+    // How long `shutdown` waits for the state check loop by default
+    pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+    // How often `wait_for_ready` probes the critical checkers
+    const READY_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
     //Handler for /actuator/info endpoint
     pub async fn info_handler(Extension(state): Extension<ActuatorState>) -> impl IntoResponse {
-        let is_ready = state.is_ready
-            && check_all_health(&state.health_checkers, |checker| checker.is_ready()).await;
-        let is_alive = state.is_alive
-            && check_all_health(&state.health_checkers, |checker| checker.is_alive()).await;
+        let is_ready = state.check_ready().await;
+        let is_alive = state.check_alive().await;
+        let body = serde_json::Value::Object(state.info.lock().unwrap().clone());
 
         Response::builder()
             .status(if is_ready && is_alive {
@@ -30,26 +55,65 @@ pub mod api {
                 StatusCode::SERVICE_UNAVAILABLE
             })
             .header("Content-Type", "application/json")
-            .body(Body::empty())
+            .body(body.to_string())
             .unwrap()
     }
 
-    // Placeholder health handler function
-    pub async fn health_handler(Extension(state): Extension<ActuatorState>) -> impl IntoResponse {
-        let is_ready = state.is_ready
-            && check_all_health(&state.health_checkers, |checker| checker.is_ready()).await;
-        let is_alive = state.is_alive
-            && check_all_health(&state.health_checkers, |checker| checker.is_alive()).await;
+    // Build metadata of this crate, captured at compile time; apps report their own via
+    // `ActuatorState::with_info`
+    fn default_info() -> InfoMap {
+        let mut info = InfoMap::new();
+        info.insert(
+            "build".to_string(),
+            json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "name": env!("CARGO_PKG_NAME"),
+                "rustc": crate::build_info::RUSTC_VERSION,
+            }),
+        );
+        info.insert(
+            "git".to_string(),
+            json!({ "commit": crate::build_info::GIT_HASH }),
+        );
+        info
+    }
+
+    type InfoMap = serde_json::Map<String, serde_json::Value>;
+
+    // Query parameters for /actuator/health
+    #[derive(Debug, Default, Deserialize)]
+    pub struct HealthQuery {
+        // `always200` keeps the HTTP status at 200 and reports health only in the body
+        pub status_code: Option<String>,
+    }
+
+    // Handler for /actuator/health: the aggregate status along with each named checker, e.g.
+    // `{"status": "UP", "components": {"database": {"status": "UP"}}}`
+    pub async fn health_handler(
+        query: Option<Query<HealthQuery>>,
+        Extension(state): Extension<ActuatorState>,
+    ) -> impl IntoResponse {
+        let is_ready = state.check_ready().await;
+        let is_alive = state.check_alive().await;
         let status = if is_ready && is_alive { "UP" } else { "DOWN" };
+        let components = state
+            .component_health()
+            .await
+            .into_iter()
+            .map(|(name, up)| (name, json!({ "status": if up { "UP" } else { "DOWN" } })))
+            .collect::<serde_json::Map<_, _>>();
+
+        let Query(query) = query.unwrap_or_default();
+        let always_ok = query.status_code.as_deref() == Some("always200");
 
         Response::builder()
-            .status(if is_ready && is_alive {
+            .status(if always_ok || (is_ready && is_alive) {
                 StatusCode::OK
             } else {
                 StatusCode::SERVICE_UNAVAILABLE
             })
             .header("Content-Type", "application/json")
-            .body(json!({ "status": status }).to_string())
+            .body(json!({ "status": status, "components": components }).to_string())
             .unwrap()
     }
 
@@ -57,8 +121,7 @@ pub mod api {
     pub async fn readiness_handler(
         Extension(state): Extension<ActuatorState>,
     ) -> impl IntoResponse {
-        let is_ready = state.is_ready
-            && check_all_health(&state.health_checkers, |checker| checker.is_ready()).await;
+        let is_ready = state.check_ready().await;
         let body = json!({ "status": if is_ready { "UP" } else { "DOWN" } });
 
         Response::builder()
@@ -71,10 +134,61 @@ pub mod api {
             .unwrap()
     }
 
+    // Handler for POST /actuator/drain: stop taking traffic ahead of shutdown
+    pub async fn drain_handler(Extension(state): Extension<ActuatorState>) -> impl IntoResponse {
+        state.start_draining();
+
+        Response::builder()
+            .status(StatusCode::ACCEPTED)
+            .header("Content-Type", "application/json")
+            .body(json!({ "status": "DRAINING" }).to_string())
+            .unwrap()
+    }
+
+    // Body of POST /actuator/maintenance; without one, maintenance mode is toggled
+    #[derive(Debug, Deserialize)]
+    pub struct MaintenanceRequest {
+        pub enabled: bool,
+    }
+
+    // Handler for POST /actuator/maintenance: turn business routes away with 503 while
+    // actuator routes keep serving orchestration
+    pub async fn maintenance_handler(
+        Extension(state): Extension<ActuatorState>,
+        request: Option<Json<MaintenanceRequest>>,
+    ) -> impl IntoResponse {
+        let enabled = match request {
+            Some(Json(request)) => request.enabled,
+            None => !state.is_in_maintenance(),
+        };
+        state.set_maintenance(enabled);
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/json")
+            .body(json!({ "maintenance": enabled }).to_string())
+            .unwrap()
+    }
+
+    async fn reject_during_maintenance(
+        State(state): State<ActuatorState>,
+        request: Request,
+        next: Next,
+    ) -> Response<Body> {
+        if !state.is_in_maintenance() {
+            return next.run(request).await;
+        }
+
+        Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header("Content-Type", "application/json")
+            .body(Body::from(json!({ "status": "MAINTENANCE" }).to_string()))
+            .unwrap()
+    }
+
     // Handler for /actuator/health/liveness endpoint
     pub async fn liveness_handler(Extension(state): Extension<ActuatorState>) -> impl IntoResponse {
-        let is_alive = state.is_alive
-            && check_all_health(&state.health_checkers, |checker| checker.is_alive()).await;
+        let is_alive = state.check_alive().await;
         let body = json!({ "status": if is_alive { "UP" } else { "DOWN" } });
 
         Response::builder()
@@ -87,35 +201,200 @@ pub mod api {
             .unwrap()
     }
 
-    async fn check_all_health<F>(health_checkers: &ActuatorStateDb, check_fn: F) -> bool
+    // Run `check_fn` against every checker, keyed by the name it was registered under
+    async fn check_all_health<F>(
+        health_checkers: &ActuatorStateDb,
+        check_fn: F,
+    ) -> BTreeMap<String, bool>
     where
-        F: Fn(&dyn StateChecker) -> bool,
+        F: for<'a> Fn(&'a dyn StateChecker) -> BoxFuture<'a, bool>,
     {
-        let mut is_health = true;
-        for (_, checker) in health_checkers.iter() {
-            let checker = checker.lock().unwrap();
-            if !check_fn(&**checker) {
-                is_health = false;
-                break;
-            }
+        let mut statuses = BTreeMap::new();
+        for (name, checker) in checker_list(health_checkers) {
+            let checker = checker.lock().await;
+            statuses.insert(name, check_fn(&**checker).await);
         }
-        is_health
+        statuses
     }
 
-    // Define a trait for health checkers
+    fn all_up(statuses: &BTreeMap<String, bool>) -> bool {
+        statuses.values().all(|up| *up)
+    }
+
+    type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+    // Define a trait for health checkers. Checks may await their dependency, e.g. ping a
+    // database, without blocking the runtime
+    #[async_trait]
     pub trait StateChecker: Send + Sync + Debug {
-        fn is_ready(&self) -> bool;
-        fn is_alive(&self) -> bool;
+        async fn is_ready(&self) -> bool;
+        async fn is_alive(&self) -> bool;
+    }
+
+    // Ready and alive while the filesystem holding `path` has at least `min_free_bytes`
+    // available; a path that can't be inspected counts as full
+    #[derive(Debug, Clone)]
+    pub struct DiskSpaceHealthCheck {
+        path: PathBuf,
+        min_free_bytes: u64,
+    }
+
+    impl DiskSpaceHealthCheck {
+        pub fn new(path: impl Into<PathBuf>, min_free_bytes: u64) -> Self {
+            DiskSpaceHealthCheck {
+                path: path.into(),
+                min_free_bytes,
+            }
+        }
+
+        fn has_space(&self) -> bool {
+            match fs2::available_space(&self.path) {
+                Ok(available) => available >= self.min_free_bytes,
+                Err(err) => {
+                    log::warn!(
+                        "failed to read free space of {}: {err}",
+                        self.path.display()
+                    );
+                    false
+                }
+            }
+        }
+    }
+
+    #[async_trait]
+    impl StateChecker for DiskSpaceHealthCheck {
+        async fn is_ready(&self) -> bool {
+            self.has_space()
+        }
+
+        async fn is_alive(&self) -> bool {
+            self.has_space()
+        }
+    }
+
+    // Behind an async mutex, so a checker stays locked while its check is awaited
+    type SharedChecker = Arc<tokio::sync::Mutex<Box<dyn StateChecker>>>;
+
+    // Collects health checkers by name and hands them to `ActuatorState::with_health_checkers`,
+    // doing the wrapping `add_health_checker` expects
+    #[derive(Debug, Default)]
+    pub struct HealthCheckerRegistry {
+        checkers: Vec<(String, SharedChecker)>,
+        critical: HashSet<String>,
+    }
+
+    impl HealthCheckerRegistry {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn register(
+            mut self,
+            name: impl Into<String>,
+            checker: impl StateChecker + 'static,
+        ) -> Self {
+            self.checkers.push((
+                name.into(),
+                Arc::new(tokio::sync::Mutex::new(Box::new(checker))),
+            ));
+            self
+        }
+
+        // Register a checker that must be ready before the service starts, see
+        // `ActuatorState::wait_for_ready`
+        pub fn register_critical(
+            mut self,
+            name: impl Into<String>,
+            checker: impl StateChecker + 'static,
+        ) -> Self {
+            let name = name.into();
+            self.critical.insert(name.clone());
+            self.register(name, checker)
+        }
+    }
+
+    // Shared by every clone, including the one held by the state check loop, so checkers
+    // can be added at any time
+    type ActuatorStateDb = Arc<Mutex<HashMap<String, SharedChecker>>>;
+
+    // Snapshot the registered checkers, so none are checked while the registry is locked
+    fn checker_list(health_checkers: &ActuatorStateDb) -> Vec<(String, SharedChecker)> {
+        health_checkers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, checker)| (name.clone(), checker.clone()))
+            .collect()
+    }
+
+    // Returned by `wait_for_ready` when critical checkers are still DOWN at the deadline
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct NotReadyError {
+        pub down: Vec<String>,
+        pub timeout: Duration,
+    }
+
+    impl std::fmt::Display for NotReadyError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "critical health checkers still DOWN after {:?}: {}",
+                self.timeout,
+                self.down.join(", ")
+            )
+        }
     }
 
-    type ActuatorStateDb = Arc<HashMap<String, Arc<Mutex<Box<dyn StateChecker>>>>>;
+    impl std::error::Error for NotReadyError {}
+
+    type HealthChangeFn = Arc<dyn Fn(bool) + Send + Sync>;
+
+    #[derive(Clone, Default)]
+    struct HealthChangeListener(Arc<Mutex<Option<HealthChangeFn>>>);
+
+    impl Debug for HealthChangeListener {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("HealthChangeListener")
+        }
+    }
 
     // ActuatorState struct to manage health checkers and routes
     #[derive(Debug, Clone)]
     pub struct ActuatorState {
         health_checkers: ActuatorStateDb,
+        // Names of the checkers `wait_for_ready` waits for; shared like the checkers
+        critical_checkers: Arc<Mutex<HashSet<String>>>,
         state_check_sender: broadcast::Sender<()>,
         state_check_receiver: Arc<Mutex<broadcast::Receiver<()>>>,
+        state_check_count: Arc<AtomicU64>,
+        // Debug overrides pinning readiness/liveness regardless of checkers
+        force_ready: Option<bool>,
+        force_live: Option<bool>,
+        health_change_listener: HealthChangeListener,
+        // Body of /actuator/info, see `with_info`; shared with clones
+        info: Arc<Mutex<InfoMap>>,
+        // Set by `start_draining`; shared with clones so every handler sees it
+        draining: Arc<AtomicBool>,
+        // Set via POST /actuator/maintenance, see `maintenance_layer`; shared like `draining`
+        maintenance: Arc<AtomicBool>,
+        // Base period of the scheduled checks, in milliseconds; shared with clones so the
+        // background loop picks up changes
+        state_check_interval_ms: Arc<AtomicU64>,
+        // Upper bound of the random delay added to each scheduled check, in milliseconds;
+        // shared with clones like the interval
+        state_check_jitter_ms: Arc<AtomicU64>,
+        // Wait a full interval before the first scheduled check, giving dependencies time
+        // to come up; shared with clones like the jitter
+        skip_first_check: bool,
+        // Consecutive passing state checks required before readiness recovers from a
+        // failure, and how many are still outstanding; shared with clones like the jitter
+        ready_recovery_checks: Arc<AtomicU64>,
+        ready_recovery_remaining: Arc<AtomicU64>,
+        // Stops the state check loop; the task handle and timeout are shared with clones
+        // so any of them can shut it down
+        shutdown_sender: Arc<watch::Sender<bool>>,
+        shutdown_timeout_ms: Arc<AtomicU64>,
+        state_check_task: Arc<Mutex<Option<JoinHandle<()>>>>,
         is_ready: bool,
         is_alive: bool,
         is_health: bool,
@@ -127,9 +406,27 @@ pub mod api {
             let state_clone_receiver = Arc::new(Mutex::new(state_check_receiver));
 
             ActuatorState {
-                health_checkers: Arc::new(HashMap::new()),
+                health_checkers: Arc::new(Mutex::new(HashMap::new())),
+                critical_checkers: Arc::new(Mutex::new(HashSet::new())),
                 state_check_sender,
                 state_check_receiver: state_clone_receiver.clone(),
+                state_check_count: Arc::new(AtomicU64::new(0)),
+                force_ready: None,
+                force_live: None,
+                health_change_listener: HealthChangeListener::default(),
+                info: Arc::new(Mutex::new(default_info())),
+                draining: Arc::new(AtomicBool::new(false)),
+                maintenance: Arc::new(AtomicBool::new(false)),
+                state_check_interval_ms: Arc::new(AtomicU64::new(
+                    STATE_CHECK_INTERVAL.as_millis() as u64
+                )),
+                state_check_jitter_ms: Arc::new(AtomicU64::new(0)),
+                skip_first_check: false,
+                ready_recovery_checks: Arc::new(AtomicU64::new(0)),
+                ready_recovery_remaining: Arc::new(AtomicU64::new(0)),
+                shutdown_sender: Arc::new(watch::channel(false).0),
+                shutdown_timeout_ms: Arc::new(AtomicU64::new(SHUTDOWN_TIMEOUT.as_millis() as u64)),
+                state_check_task: Arc::new(Mutex::new(None)),
                 is_ready: true,
                 is_alive: true,
                 is_health: true,
@@ -140,66 +437,318 @@ pub mod api {
     impl ActuatorState {
         // Create a new ActuatorState instance
         pub fn new() -> Self {
-            let state = ActuatorState::default();
+            ActuatorState::new_with(|state| state)
+        }
+
+        // Like `new`, with `configure` applied before the state check loop starts, as
+        // options such as `with_skip_first_check` require
+        pub fn new_with(configure: impl FnOnce(Self) -> Self) -> Self {
+            let state = configure(ActuatorState::default());
             let mut state_clone = state.clone();
             let state_clone_sender = state_clone.state_check_sender.clone(); // Clone the sender
+            let shutdown_receiver = state_clone.shutdown_sender.subscribe();
 
-            tokio::spawn(async move {
+            let task = tokio::spawn(async move {
                 let state_clone_receiver = state_clone_sender.subscribe();
-                state_clone.state_check_loop(state_clone_receiver).await;
+                state_clone
+                    .state_check_loop(state_clone_receiver, shutdown_receiver)
+                    .await;
             });
+            *state.state_check_task.lock().unwrap() = Some(task);
 
             state
         }
 
-        async fn state_check_loop(&mut self, mut receiver: broadcast::Receiver<()>) {
-            let mut interval = tokio::time::interval(Duration::from_secs(10));
+        async fn state_check_loop(
+            &mut self,
+            mut receiver: broadcast::Receiver<()>,
+            mut shutdown_receiver: watch::Receiver<bool>,
+        ) {
+            // First check runs immediately unless skipped, later ones every interval plus jitter.
+            // Each check is scheduled from when the previous one ran on tokio's monotonic
+            // clock, so wall clock jumps don't move it and a stall of several intervals is
+            // followed by one check rather than a burst of catch-up checks
+            let scheduled = tokio::time::sleep(Duration::ZERO);
+            tokio::pin!(scheduled);
+            let mut is_first_tick = true;
 
             loop {
-                // Check for messages on the receiver alongside the interval
+                // Check for messages on the receiver alongside the schedule
                 tokio::select! {
-                    _ = interval.tick() => {
-                        // Scheduled check
-                        self.check_all_health().await;
+                    _ = &mut scheduled => {
+                        let skip = std::mem::take(&mut is_first_tick)
+                            && self.skip_first_check;
+                        if skip {
+                            log::debug!("skipping the state check at startup");
+                        } else {
+                            // Scheduled check
+                            self.check_all_health().await;
+                        }
+                        scheduled
+                            .as_mut()
+                            .reset(tokio::time::Instant::now() + self.next_state_check_delay());
                     }
-                    _ = receiver.recv() => {
+                    received = receiver.recv() => match received {
                         // Manual check triggered via sender
-                        self.check_all_health().await;
+                        Ok(()) => self.check_all_health().await,
+                        // Triggers sent faster than we consume them are coalesced into one check
+                        Err(RecvError::Lagged(skipped)) => {
+                            log::debug!("coalesced {skipped} state check triggers");
+                            self.check_all_health().await;
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = shutdown_receiver.changed() => {
+                        log::debug!("state check loop shutting down");
+                        break;
                     }
                 }
             }
         }
 
-        async fn check_all_health(&mut self) {
-            let mut new_check = true;
-            self.is_health = true;
+        // Delay until the next scheduled check, randomized so replicas don't probe in lockstep
+        pub(crate) fn next_state_check_delay(&self) -> Duration {
+            let interval_ms = self.state_check_interval_ms.load(Ordering::Relaxed);
+            let jitter_ms = self.state_check_jitter_ms.load(Ordering::Relaxed);
+            Duration::from_millis(interval_ms + rand::random::<u64>() % (jitter_ms + 1))
+        }
+
+        pub(crate) async fn check_all_health(&mut self) {
+            self.state_check_count.fetch_add(1, Ordering::Relaxed);
+            let was_health = self.is_health;
+
+            let checkers_ready = self.update_health().await;
+            self.record_ready_check(checkers_ready);
+
+            // Notify once per transition, not on every check
+            if was_health != self.is_health {
+                let (from, to) = if self.is_health {
+                    ("DOWN", "UP")
+                } else {
+                    ("UP", "DOWN")
+                };
+                log::warn!("aggregate health changed from {from} to {to}");
+                let listener = self.health_change_listener.0.lock().unwrap().clone();
+                if let Some(listener) = listener {
+                    listener(self.is_health);
+                }
+            }
+        }
+
+        // Restart the recovery countdown on a failure, otherwise count down towards UP
+        fn record_ready_check(&self, checkers_ready: bool) {
+            if checkers_ready {
+                let _ = self.ready_recovery_remaining.fetch_update(
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    |remaining| remaining.checked_sub(1),
+                );
+            } else {
+                self.ready_recovery_remaining.store(
+                    self.ready_recovery_checks.load(Ordering::Relaxed),
+                    Ordering::Relaxed,
+                );
+            }
+        }
+
+        // Probe every checker once, returning whether all of them are ready
+        async fn update_health(&mut self) -> bool {
+            let mut statuses = Vec::new();
+            for (_, checker) in checker_list(&self.health_checkers) {
+                let checker = checker.lock().await;
+                statuses.push((checker.is_ready().await, checker.is_alive().await));
+            }
+
+            // The first unhealthy checker decides which state goes down
+            match statuses
+                .iter()
+                .find(|(is_ready, is_alive)| !is_ready || !is_alive)
+            {
+                Some((_, false)) => {
+                    self.is_alive = false;
+                    self.is_health = false;
+                }
+                Some(_) => {
+                    self.is_ready = false;
+                    self.is_health = false;
+                }
+                None => {
+                    self.is_ready = true;
+                    self.is_alive = true;
+                    self.is_health = true;
+                }
+            }
+
+            statuses.iter().all(|(is_ready, _)| *is_ready)
+        }
+
+        // Call `listener` with the new aggregate health whenever it flips between UP and DOWN,
+        // e.g. to post to a webhook
+        pub fn with_health_change_listener<F>(self, listener: F) -> Self
+        where
+            F: Fn(bool) + Send + Sync + 'static,
+        {
+            // Shared with clones, so the background check loop sees it too
+            *self.health_change_listener.0.lock().unwrap() = Some(Arc::new(listener));
+            self
+        }
+
+        // Run scheduled checks every `interval` instead of every 10s. Takes effect from the
+        // next scheduled check
+        pub fn with_state_check_interval(self, interval: Duration) -> Self {
+            self.state_check_interval_ms
+                .store(interval.as_millis() as u64, Ordering::Relaxed);
+            self
+        }
+
+        // Report `value` under `key` in /actuator/info, replacing the default `build` or `git`
+        // entry when `key` is one of them
+        pub fn with_info(self, key: impl Into<String>, value: serde_json::Value) -> Self {
+            self.info.lock().unwrap().insert(key.into(), value);
+            self
+        }
+
+        // Add up to `jitter` to each scheduled check interval
+        pub fn with_state_check_jitter(self, jitter: Duration) -> Self {
+            self.state_check_jitter_ms
+                .store(jitter.as_millis() as u64, Ordering::Relaxed);
+            self
+        }
+
+        // Skip the check the background loop would run at startup. Read once when the loop
+        // starts, so set it through `new_with`; it has no effect on a running loop
+        pub fn with_skip_first_check(mut self, skip_first_check: bool) -> Self {
+            self.skip_first_check = skip_first_check;
+            self
+        }
+
+        // After a failed readiness check, keep readiness DOWN until this many consecutive
+        // state checks pass, so a flapping dependency doesn't flap readiness with it.
+        // Failures still take readiness DOWN immediately; 0 recovers on the first pass
+        pub fn with_ready_recovery_checks(self, checks: u64) -> Self {
+            self.ready_recovery_checks.store(checks, Ordering::Relaxed);
+            self
+        }
 
-            for (_, checker) in self.health_checkers.iter() {
-                let checker = checker.lock().unwrap();
-                let is_ready = checker.is_ready();
-                let is_alive = checker.is_alive();
+        // How long `shutdown` waits for the state check loop to exit
+        pub fn with_shutdown_timeout(self, timeout: Duration) -> Self {
+            self.shutdown_timeout_ms
+                .store(timeout.as_millis() as u64, Ordering::Relaxed);
+            self
+        }
 
-                if new_check && !is_alive {
-                    self.is_alive = is_alive;
-                    self.is_health = is_alive;
-                    return; // Early return if unhealthy
-                } else if new_check && !is_ready {
-                    self.is_ready = is_ready;
-                    self.is_health = is_ready;
-                    return; // Early return if unhealthy
+        // Stop the state check loop started by `new()` and wait up to the shutdown timeout
+        // for it to exit. Returns whether it exited in time; a check stuck in a blocking
+        // checker keeps it running
+        pub async fn shutdown(&self) -> bool {
+            self.shutdown_sender.send_replace(true);
+
+            let Some(task) = self.state_check_task.lock().unwrap().take() else {
+                return true;
+            };
+            let timeout = Duration::from_millis(self.shutdown_timeout_ms.load(Ordering::Relaxed));
+            match tokio::time::timeout(timeout, task).await {
+                Ok(_) => true,
+                Err(_) => {
+                    log::warn!("state check loop did not stop within {timeout:?}");
+                    false
                 }
+            }
+        }
+
+        // Pin readiness to the given value, ignoring health checkers
+        pub fn with_force_ready(mut self, force_ready: Option<bool>) -> Self {
+            self.force_ready = force_ready;
+            self
+        }
+
+        // Pin liveness to the given value, ignoring health checkers
+        pub fn with_force_live(mut self, force_live: Option<bool>) -> Self {
+            self.force_live = force_live;
+            self
+        }
+
+        // Report readiness DOWN from now on while liveness is unaffected, so orchestrators
+        // stop routing traffic here without restarting the instance
+        pub fn start_draining(&self) {
+            if !self.draining.swap(true, Ordering::Relaxed) {
+                log::warn!("draining: readiness reports DOWN from now on");
+            }
+        }
+
+        pub fn is_draining(&self) -> bool {
+            self.draining.load(Ordering::Relaxed)
+        }
+
+        pub fn set_maintenance(&self, enabled: bool) {
+            if self.maintenance.swap(enabled, Ordering::Relaxed) != enabled {
+                let state = if enabled { "enabled" } else { "disabled" };
+                log::warn!("maintenance mode {state}");
+            }
+        }
+
+        pub fn is_in_maintenance(&self) -> bool {
+            self.maintenance.load(Ordering::Relaxed)
+        }
+
+        // Answer every route of `router` with 503 while maintenance mode is on; meant for
+        // the business routes, leaving the actuator routes reachable
+        pub fn maintenance_layer<S: Clone + Send + Sync + 'static>(
+            &self,
+            router: Router<S>,
+        ) -> Router<S> {
+            router.layer(middleware::from_fn_with_state(
+                self.clone(),
+                reject_during_maintenance,
+            ))
+        }
+
+        async fn check_ready(&self) -> bool {
+            if self.is_draining() {
+                return false;
+            }
+            if let Some(force_ready) = self.force_ready {
+                return force_ready;
+            }
+            let checkers_ready = self.is_ready
+                && all_up(
+                    &check_all_health(&self.health_checkers, |checker| checker.is_ready()).await,
+                );
+            if !checkers_ready {
+                self.record_ready_check(false);
+                return false;
+            }
+            self.ready_recovery_remaining.load(Ordering::Relaxed) == 0
+        }
 
-                new_check = self.is_health; // Update new_check only if still healthy
+        async fn check_alive(&self) -> bool {
+            if let Some(force_live) = self.force_live {
+                return force_live;
             }
+            self.is_alive
+                && all_up(
+                    &check_all_health(&self.health_checkers, |checker| checker.is_alive()).await,
+                )
+        }
 
-            // If loop finishes without early return, set to healthy
-            self.is_ready = true;
-            self.is_alive = true;
+        // Whether each checker is both ready and alive, by name
+        async fn component_health(&self) -> BTreeMap<String, bool> {
+            check_all_health(&self.health_checkers, |checker| {
+                Box::pin(async move { checker.is_ready().await && checker.is_alive().await })
+            })
+            .await
         }
 
         // Trigger state check manually
         pub fn trigger_state_check(&self) {
-            let _ = self.state_check_sender.send(());
+            if self.state_check_sender.send(()).is_err() {
+                log::warn!("state check trigger dropped: no state check loop is listening");
+            }
+        }
+
+        // Number of state checks run by the background loop
+        pub fn state_check_count(&self) -> u64 {
+            self.state_check_count.load(Ordering::Relaxed)
         }
 
         // create state check receiver manually
@@ -208,21 +757,291 @@ pub mod api {
         }
 
         // Add a health checker
-        pub fn add_health_checker(
-            &mut self,
-            name: String,
-            checker: Arc<Mutex<Box<dyn StateChecker>>>,
-        ) {
-            if let Some(health_checkers) = Arc::get_mut(&mut self.health_checkers) {
-                health_checkers.insert(name, checker);
-                println!("{:?}", health_checkers);
-            } else {
-                // Handle the case where the value is None
-                println!("Health check value is not available");
+        pub fn add_health_checker(&mut self, name: String, checker: SharedChecker) {
+            log::debug!("registered health checker {name}");
+            self.health_checkers.lock().unwrap().insert(name, checker);
+        }
+
+        // Add every checker collected in `registry`
+        pub fn with_health_checkers(mut self, registry: HealthCheckerRegistry) -> Self {
+            for (name, checker) in registry.checkers {
+                if registry.critical.contains(&name) {
+                    self.add_critical_health_checker(name, checker);
+                } else {
+                    self.add_health_checker(name, checker);
+                }
+            }
+            self
+        }
+
+        // Remove a health checker, e.g. when its subsystem shuts down, returning it if it
+        // was registered
+        pub fn remove_health_checker(&mut self, name: &str) -> Option<SharedChecker> {
+            self.critical_checkers.lock().unwrap().remove(name);
+            let checker = self.health_checkers.lock().unwrap().remove(name);
+            if checker.is_some() {
+                log::debug!("removed health checker {name}");
+            }
+            checker
+        }
+
+        // Add a health checker that must be ready before the service starts, see
+        // `wait_for_ready`
+        pub fn add_critical_health_checker(&mut self, name: String, checker: SharedChecker) {
+            self.critical_checkers.lock().unwrap().insert(name.clone());
+            self.add_health_checker(name, checker);
+        }
+
+        // Probe the critical checkers until all of them are ready, giving up after
+        // `timeout`. Meant to run before binding, so a service whose critical
+        // dependencies are DOWN fails fast instead of starting unready
+        pub async fn wait_for_ready(&self, timeout: Duration) -> Result<(), NotReadyError> {
+            let deadline = tokio::time::Instant::now() + timeout;
+            loop {
+                let down = self.critical_down().await;
+                if down.is_empty() {
+                    return Ok(());
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    return Err(NotReadyError { down, timeout });
+                }
+                log::debug!("waiting for critical health checkers: {}", down.join(", "));
+                tokio::time::sleep_until(
+                    deadline.min(tokio::time::Instant::now() + READY_POLL_INTERVAL),
+                )
+                .await;
+            }
+        }
+
+        // Names of the critical checkers that aren't ready, sorted
+        async fn critical_down(&self) -> Vec<String> {
+            let critical = self.critical_checkers.lock().unwrap().clone();
+            let statuses =
+                check_all_health(&self.health_checkers, |checker| checker.is_ready()).await;
+            statuses
+                .into_iter()
+                .filter(|(name, ready)| !ready && critical.contains(name))
+                .map(|(name, _)| name)
+                .collect()
+        }
+    }
+
+    // Source of the wall clock time, so time dependent behavior can be tested without
+    // sleeping; elapsed times keep using `Instant`
+    pub trait Clock: Debug + Send + Sync {
+        fn now(&self) -> SystemTime;
+    }
+
+    pub type SharedClock = Arc<dyn Clock>;
+
+    // The real clock
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SystemClock;
+
+    impl Clock for SystemClock {
+        fn now(&self) -> SystemTime {
+            SystemTime::now()
+        }
+    }
+
+    // A clock that only moves when told to; clones share the same time
+    #[derive(Debug, Clone)]
+    pub struct MockClock {
+        now: Arc<Mutex<SystemTime>>,
+    }
+
+    impl MockClock {
+        pub fn new(now: SystemTime) -> Self {
+            MockClock {
+                now: Arc::new(Mutex::new(now)),
+            }
+        }
+
+        pub fn advance(&self, by: Duration) {
+            *self.now.lock().unwrap() += by;
+        }
+
+        pub fn set(&self, now: SystemTime) {
+            *self.now.lock().unwrap() = now;
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> SystemTime {
+            *self.now.lock().unwrap()
+        }
+    }
+
+    // A recorded request, as exposed by /actuator/httptrace
+    #[derive(Debug, Clone, Serialize)]
+    pub struct HttpTraceEntry {
+        pub timestamp_ms: u128,
+        pub method: String,
+        pub path: String,
+        pub status: u16,
+        pub time_taken_ms: u128,
+    }
+
+    // Bounded ring buffer of the most recent requests
+    #[derive(Debug, Clone)]
+    pub struct HttpTrace {
+        entries: Arc<Mutex<VecDeque<HttpTraceEntry>>>,
+        capacity: usize,
+        // Stamps the entries, see `with_clock`
+        clock: SharedClock,
+    }
+
+    impl HttpTrace {
+        pub fn new(capacity: usize) -> Self {
+            HttpTrace {
+                entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+                capacity,
+                clock: Arc::new(SystemClock),
+            }
+        }
+
+        // Stamp entries with `clock` rather than the system clock
+        pub fn with_clock(mut self, clock: SharedClock) -> Self {
+            self.clock = clock;
+            self
+        }
+
+        // Record requests handled by the router; apply after all routes are added
+        pub fn layer<S: Clone + Send + Sync + 'static>(&self, router: Router<S>) -> Router<S> {
+            router.layer(middleware::from_fn_with_state(
+                self.clone(),
+                record_http_trace,
+            ))
+        }
+
+        pub fn record(&self, entry: HttpTraceEntry) {
+            if self.capacity == 0 {
+                return;
+            }
+            let mut entries = self.entries.lock().unwrap();
+            while entries.len() >= self.capacity {
+                entries.pop_front(); // Evict the oldest
             }
+            entries.push_back(entry);
+        }
+
+        // Recorded requests, oldest first
+        pub fn entries(&self) -> Vec<HttpTraceEntry> {
+            self.entries.lock().unwrap().iter().cloned().collect()
+        }
+    }
+
+    async fn record_http_trace(
+        State(http_trace): State<HttpTrace>,
+        request: Request,
+        next: Next,
+    ) -> axum::response::Response {
+        let method = request.method().to_string();
+        let path = request.uri().path().to_string();
+        let timestamp_ms = http_trace
+            .clock
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let started = Instant::now();
+
+        let response = next.run(request).await;
+
+        http_trace.record(HttpTraceEntry {
+            timestamp_ms,
+            method,
+            path,
+            status: response.status().as_u16(),
+            time_taken_ms: started.elapsed().as_millis(),
+        });
+        response
+    }
+
+    // Upper bounds of the request latency histogram buckets, in seconds
+    const LATENCY_BUCKETS: [f64; 12] = [
+        0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+    ];
+
+    // Handle to the process-wide Prometheus recorder, installed on first use; `metrics`
+    // allows a single global recorder, so every router shares it
+    fn prometheus_handle() -> PrometheusHandle {
+        static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+        HANDLE
+            .get_or_init(|| {
+                let recorder = PrometheusBuilder::new()
+                    .set_buckets_for_metric(
+                        Matcher::Full("http_request_duration_seconds".to_string()),
+                        &LATENCY_BUCKETS,
+                    )
+                    .expect("latency buckets are not empty")
+                    .build_recorder();
+                let handle = recorder.handle();
+                if metrics::set_global_recorder(recorder).is_err() {
+                    log::warn!(
+                        "another metrics recorder is installed; /actuator/metrics stays empty"
+                    );
+                }
+                handle
+            })
+            .clone()
+    }
+
+    // Record request count, in-flight requests and per-route latency for /actuator/metrics;
+    // apply after all routes are added, like `HttpTrace::layer`
+    pub fn metrics_layer<S: Clone + Send + Sync + 'static>(router: Router<S>) -> Router<S> {
+        // Install the recorder up front, so the first requests are counted
+        prometheus_handle();
+        router.layer(middleware::from_fn(record_http_metrics))
+    }
+
+    // Decrements the in-flight gauge even if the request is cancelled
+    struct InFlightGuard;
+
+    impl Drop for InFlightGuard {
+        fn drop(&mut self) {
+            metrics::gauge!("http_requests_in_flight").decrement(1.0);
         }
     }
 
+    async fn record_http_metrics(request: Request, next: Next) -> axum::response::Response {
+        let method = request.method().to_string();
+        // The route template rather than the raw path, so ids don't explode the label set
+        let path = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let started = Instant::now();
+
+        metrics::gauge!("http_requests_in_flight").increment(1.0);
+        let _in_flight = InFlightGuard;
+        let response = next.run(request).await;
+
+        let status = response.status().as_u16().to_string();
+        metrics::counter!(
+            "http_requests_total",
+            "method" => method.clone(),
+            "path" => path.clone(),
+            "status" => status,
+        )
+        .increment(1);
+        metrics::histogram!(
+            "http_request_duration_seconds",
+            "method" => method,
+            "path" => path,
+        )
+        .record(started.elapsed().as_secs_f64());
+        response
+    }
+
+    async fn metrics_handler() -> impl IntoResponse {
+        Response::builder()
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(prometheus_handle().render())
+            .unwrap()
+    }
+
     #[derive(Debug)]
     pub struct ActuatorRouterBuilder<RT> {
         router: Router<RT>,
@@ -244,14 +1063,16 @@ pub mod api {
             self
         }
 
-        //TODO: need to figure out how to create route dinamically
-        // pub fn with_route<H, S>(mut self, uri: &str, handler: H) -> Self
-        // where
-        //     H: Handler<(), Extension<S>>,
-        //     S: Clone + Send + Sync + 'static, {
-        //     self.router = self.router.route(uri, get(handler));
-        //     self
-        // }
+        // Add a GET route for a custom endpoint, e.g. /actuator/threaddump. The handler can
+        // extract the `ActuatorState` extension like the built-in ones
+        pub fn with_custom_route<H, T>(mut self, path: &str, handler: H) -> Self
+        where
+            H: Handler<T, RT>,
+            T: 'static,
+        {
+            self.router = self.router.route(path, get(handler));
+            self
+        }
 
         pub fn with_readiness_route(mut self) -> Self {
             self.router = self
@@ -277,16 +1098,45 @@ pub mod api {
             self
         }
 
-        pub fn build(self) -> Router<RT> {
-            self.router
+        pub fn with_drain_route(mut self) -> Self {
+            self.router = self.router.route("/actuator/drain", post(drain_handler));
+            self
         }
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // Toggle maintenance mode; routes are turned away by `ActuatorState::maintenance_layer`
+        pub fn with_maintenance_route(mut self) -> Self {
+            self.router = self
+                .router
+                .route("/actuator/maintenance", post(maintenance_handler));
+            self
+        }
+
+        // Expose recorded requests; requests are captured by `HttpTrace::layer`
+        pub fn with_httptrace_route(mut self, http_trace: HttpTrace) -> Self {
+            self.router = self.router.route(
+                "/actuator/httptrace",
+                get(move || async move { Json(json!({ "traces": http_trace.entries() })) }),
+            );
+            self
+        }
+
+        // Expose Prometheus metrics; requests are recorded by `metrics_layer`
+        pub fn with_metrics_route(mut self) -> Self {
+            self.router = self.router.route("/actuator/metrics", get(metrics_handler));
+            self
+        }
+
+        pub fn build(self) -> Router<RT> {
+            self.router
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
     use axum::{
+        async_trait,
         body::Body,
         extract::connect_info::MockConnectInfo,
         extract::{ConnectInfo, Extension},
@@ -300,7 +1150,9 @@ mod tests {
 
     use api::{ActuatorRouterBuilder, ActuatorState, StateChecker};
     use http::Method;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
     use tower::{Service, ServiceExt}; // for `call`, `oneshot`, and `ready`
 
     pub fn app() -> Router {
@@ -368,12 +1220,13 @@ mod tests {
         alive: bool,
     }
 
+    #[async_trait]
     impl StateChecker for DatabaseHealthCheck {
-        fn is_ready(&self) -> bool {
+        async fn is_ready(&self) -> bool {
             self.ready
         }
 
-        fn is_alive(&self) -> bool {
+        async fn is_alive(&self) -> bool {
             self.alive
         }
     }
@@ -386,7 +1239,7 @@ mod tests {
         // Add health checkers
         actuator_state.add_health_checker(
             "database".to_string(),
-            Arc::new(Mutex::new(Box::new(DatabaseHealthCheck {
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
                 ready: true,
                 alive: true,
             }))),
@@ -396,7 +1249,7 @@ mod tests {
 
         actuator_state.add_health_checker(
             "database".to_string(),
-            Arc::new(Mutex::new(Box::new(DatabaseHealthCheck {
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
                 ready: false,
                 alive: false,
             }))),
@@ -406,7 +1259,7 @@ mod tests {
 
         actuator_state.add_health_checker(
             "database".to_string(),
-            Arc::new(Mutex::new(Box::new(DatabaseHealthCheck {
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
                 ready: true,
                 alive: true,
             }))),
@@ -415,6 +1268,737 @@ mod tests {
         println!("{:?}", actuator_state);
     }
 
+    #[tokio::test]
+    async fn trigger_state_check_burst() {
+        let actuator_state = api::ActuatorState::new();
+
+        // Let the background loop subscribe and run its first scheduled check
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let before = actuator_state.state_check_count();
+
+        for _ in 0..1000 {
+            actuator_state.trigger_state_check();
+        }
+
+        tokio::time::timeout(std::time::Duration::from_secs(1), async {
+            while actuator_state.state_check_count() <= before {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("triggered state checks should still run");
+    }
+
+    #[tokio::test]
+    async fn skip_first_check_delays_startup_check() {
+        let actuator_state =
+            api::ActuatorState::new_with(|state| state.with_skip_first_check(true));
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(actuator_state.state_check_count(), 0);
+
+        // Too late once the loop is running
+        let actuator_state = api::ActuatorState::new().with_skip_first_check(true);
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(actuator_state.state_check_count(), 1);
+
+        // By default the first check runs straight away
+        let actuator_state = api::ActuatorState::new();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(actuator_state.state_check_count(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn missed_state_checks_are_not_caught_up() {
+        let actuator_state = api::ActuatorState::new();
+        let settle = || async {
+            for _ in 0..10 {
+                tokio::task::yield_now().await;
+            }
+        };
+        settle().await;
+        assert_eq!(actuator_state.state_check_count(), 1);
+
+        // Ten intervals pass without the loop getting to run, e.g. a suspended host
+        tokio::time::advance(Duration::from_secs(100)).await;
+        settle().await;
+        assert_eq!(actuator_state.state_check_count(), 2);
+
+        // And the schedule resumes from the catch-up check
+        tokio::time::advance(Duration::from_secs(9)).await;
+        settle().await;
+        assert_eq!(actuator_state.state_check_count(), 2);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        settle().await;
+        assert_eq!(actuator_state.state_check_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn shutdown_stops_state_check_loop() {
+        let actuator_state =
+            api::ActuatorState::new().with_shutdown_timeout(Duration::from_millis(500));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(actuator_state.state_check_count(), 1);
+
+        let started = std::time::Instant::now();
+        assert!(actuator_state.shutdown().await);
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        // Nothing is listening for triggers any more
+        actuator_state.trigger_state_check();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(actuator_state.state_check_count(), 1);
+
+        // Shutting down again has nothing left to wait for
+        assert!(actuator_state.shutdown().await);
+    }
+
+    #[tokio::test]
+    async fn health_always200_reports_down_in_body() {
+        let mut actuator_state = api::ActuatorState::default();
+        actuator_state.add_health_checker(
+            "database".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: false,
+                alive: true,
+            }))),
+        );
+
+        let mut app = ActuatorRouterBuilder::new(app())
+            .with_health_route()
+            .with_layer(Some(Extension(actuator_state)))
+            .build()
+            .into_service();
+
+        let request = Request::builder()
+            .uri("/actuator/health")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let request = Request::builder()
+            .uri("/actuator/health?status_code=always200")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({ "status": "DOWN", "components": { "database": { "status": "DOWN" } } })
+        );
+    }
+
+    #[tokio::test]
+    async fn custom_route_is_served_with_builtin_routes() {
+        let actuator_state = api::ActuatorState::default();
+
+        let mut app = ActuatorRouterBuilder::new(app())
+            .with_health_route()
+            .with_custom_route(
+                "/actuator/threaddump",
+                |Extension(state): Extension<ActuatorState>| async move {
+                    Json(json!({ "draining": state.is_draining() }))
+                },
+            )
+            .with_layer(Some(Extension(actuator_state)))
+            .build()
+            .into_service();
+
+        let request = Request::builder()
+            .uri("/actuator/threaddump")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, json!({ "draining": false }));
+
+        let request = Request::builder()
+            .uri("/actuator/health")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn health_lists_each_component() {
+        let mut actuator_state = api::ActuatorState::default();
+        actuator_state.add_health_checker(
+            "database".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: true,
+                alive: true,
+            }))),
+        );
+        actuator_state.add_health_checker(
+            "cache".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: true,
+                alive: true,
+            }))),
+        );
+
+        let mut app = ActuatorRouterBuilder::new(app())
+            .with_health_route()
+            .with_layer(Some(Extension(actuator_state.clone())))
+            .build()
+            .into_service();
+
+        let request = Request::builder()
+            .uri("/actuator/health")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "status": "UP",
+                "components": {
+                    "cache": { "status": "UP" },
+                    "database": { "status": "UP" },
+                },
+            })
+        );
+
+        // One component going DOWN takes the aggregate with it
+        actuator_state.add_health_checker(
+            "cache".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: true,
+                alive: false,
+            }))),
+        );
+        let request = Request::builder()
+            .uri("/actuator/health")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], "DOWN");
+        assert_eq!(body["components"]["cache"]["status"], "DOWN");
+        assert_eq!(body["components"]["database"]["status"], "UP");
+    }
+
+    #[tokio::test]
+    async fn health_checker_added_after_clone_is_checked() {
+        // `new()` hands a clone to the state check loop, as does the router below
+        let mut actuator_state = api::ActuatorState::new();
+        let mut app = ActuatorRouterBuilder::new(app())
+            .with_readiness_route()
+            .with_layer(Some(Extension(actuator_state.clone())))
+            .build()
+            .into_service();
+
+        actuator_state.add_health_checker(
+            "database".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: false,
+                alive: true,
+            }))),
+        );
+
+        let request = Request::builder()
+            .uri("/actuator/health/readiness")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn disk_space_check_follows_threshold() {
+        let path = std::env::temp_dir();
+
+        let check = api::DiskSpaceHealthCheck::new(&path, 0);
+        assert!(check.is_ready().await);
+        assert!(check.is_alive().await);
+
+        let check = api::DiskSpaceHealthCheck::new(&path, u64::MAX);
+        assert!(!check.is_ready().await);
+        assert!(!check.is_alive().await);
+
+        let check = api::DiskSpaceHealthCheck::new(path.join("no-such-dir"), 0);
+        assert!(!check.is_ready().await);
+    }
+
+    #[derive(Debug)]
+    struct CountingHealthCheck(Arc<AtomicU64>);
+
+    #[async_trait]
+    impl StateChecker for CountingHealthCheck {
+        async fn is_ready(&self) -> bool {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            true
+        }
+
+        async fn is_alive(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn info_reports_build_metadata_and_custom_entries() {
+        let actuator_state = api::ActuatorState::default();
+
+        let mut app = ActuatorRouterBuilder::new(app())
+            .with_info_route()
+            .with_layer(Some(Extension(actuator_state.clone())))
+            .build()
+            .into_service();
+        let request = Request::builder()
+            .uri("/actuator/info")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["build"]["name"], "rest_actuator");
+        assert_eq!(body["build"]["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["build"]["rustc"]
+            .as_str()
+            .unwrap()
+            .starts_with("rustc"));
+        assert!(body["git"]["commit"].is_string());
+
+        let _ = actuator_state
+            .with_info("build", json!({ "name": "todos", "version": "1.2.3" }))
+            .with_info("team", json!("platform"));
+        let request = Request::builder()
+            .uri("/actuator/info")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body["build"],
+            json!({ "name": "todos", "version": "1.2.3" })
+        );
+        assert_eq!(body["team"], "platform");
+        assert!(body["git"]["commit"].is_string());
+    }
+
+    #[tokio::test]
+    async fn metrics_count_requests() {
+        let mut app = api::metrics_layer(
+            ActuatorRouterBuilder::new(app())
+                .with_metrics_route()
+                .build(),
+        )
+        .into_service();
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/json")
+            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(r#"{"metrics":true}"#))
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .uri("/actuator/metrics")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[http::header::CONTENT_TYPE],
+            "text/plain; version=0.0.4"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body
+            .lines()
+            .any(|line| line.starts_with("http_requests_total{")
+                && line.contains(r#"path="/json""#)
+                && line.contains(r#"method="POST""#)));
+        assert!(body.contains("http_request_duration_seconds_bucket{"));
+        assert!(body.contains("http_requests_in_flight"));
+    }
+
+    #[tokio::test]
+    async fn registry_registers_every_checker() {
+        let database_checks = Arc::new(AtomicU64::new(0));
+        let cache_checks = Arc::new(AtomicU64::new(0));
+        let registry = api::HealthCheckerRegistry::new()
+            .register_critical("database", CountingHealthCheck(database_checks.clone()))
+            .register("cache", CountingHealthCheck(cache_checks.clone()));
+        let mut actuator_state = api::ActuatorState::default().with_health_checkers(registry);
+
+        actuator_state.check_all_health().await;
+        // Probed once per check, not again for the recovery countdown
+        assert_eq!(database_checks.load(Ordering::SeqCst), 1);
+        assert_eq!(cache_checks.load(Ordering::SeqCst), 1);
+        assert_eq!(actuator_state.wait_for_ready(Duration::ZERO).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn removed_health_checker_is_no_longer_checked() {
+        let mut actuator_state = api::ActuatorState::default();
+        actuator_state.add_health_checker(
+            "database".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: true,
+                alive: true,
+            }))),
+        );
+        actuator_state.add_health_checker(
+            "cache".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: false,
+                alive: false,
+            }))),
+        );
+
+        let mut app = ActuatorRouterBuilder::new(app())
+            .with_health_route()
+            .with_layer(Some(Extension(actuator_state.clone())))
+            .build()
+            .into_service();
+        let mut health = || {
+            let request = Request::builder()
+                .uri("/actuator/health")
+                .body(Body::empty())
+                .unwrap();
+            let response = app.call(request);
+            async move { response.await.unwrap().status() }
+        };
+
+        actuator_state.check_all_health().await;
+        assert_eq!(health().await, StatusCode::SERVICE_UNAVAILABLE);
+
+        assert!(actuator_state.remove_health_checker("cache").is_some());
+        assert!(actuator_state.remove_health_checker("cache").is_none());
+        actuator_state.check_all_health().await;
+        assert_eq!(health().await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn wait_for_ready_fails_when_critical_checker_is_down() {
+        let mut actuator_state = api::ActuatorState::default();
+        actuator_state.add_health_checker(
+            "cache".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: false,
+                alive: true,
+            }))),
+        );
+        let timeout = Duration::from_millis(300);
+
+        // Non-critical checkers don't hold up startup
+        assert_eq!(actuator_state.wait_for_ready(timeout).await, Ok(()));
+
+        actuator_state.add_critical_health_checker(
+            "database".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: false,
+                alive: true,
+            }))),
+        );
+        let started = std::time::Instant::now();
+        let err = actuator_state.wait_for_ready(timeout).await.unwrap_err();
+        assert!(started.elapsed() < timeout * 3);
+        assert_eq!(err.down, vec!["database".to_string()]);
+        assert!(err.to_string().contains("database"));
+    }
+
+    #[tokio::test]
+    async fn forced_state_overrides_checkers() {
+        let mut actuator_state = api::ActuatorState::default();
+        actuator_state.add_health_checker(
+            "database".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
+                ready: false,
+                alive: true,
+            }))),
+        );
+        let actuator_state = actuator_state
+            .with_force_ready(Some(true))
+            .with_force_live(Some(false));
+
+        let mut app = ActuatorRouterBuilder::new(app())
+            .with_readiness_route()
+            .with_liveness_route()
+            .with_layer(Some(Extension(actuator_state)))
+            .build()
+            .into_service();
+
+        let request = Request::builder()
+            .uri("/actuator/health/readiness")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .uri("/actuator/health/liveness")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn drain_keeps_liveness_up() {
+        let mut app = ActuatorRouterBuilder::new(app())
+            .with_readiness_route()
+            .with_liveness_route()
+            .with_drain_route()
+            .with_layer(Some(Extension(ActuatorState::default())))
+            .build()
+            .into_service();
+
+        let request = Request::builder()
+            .uri("/actuator/health/readiness")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/actuator/drain")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let request = Request::builder()
+            .uri("/actuator/health/readiness")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let request = Request::builder()
+            .uri("/actuator/health/liveness")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn httptrace_keeps_most_recent_requests() {
+        let http_trace = api::HttpTrace::new(2);
+
+        let app = ActuatorRouterBuilder::new(app())
+            .with_httptrace_route(http_trace.clone())
+            .build();
+        let mut app = http_trace.layer(app).into_service();
+
+        for path in ["/first", "/second", "/third"] {
+            let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+            let response = app.ready().await.unwrap().call(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        let request = Request::builder()
+            .uri("/actuator/httptrace")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.ready().await.unwrap().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let traces = body["traces"].as_array().unwrap();
+        assert_eq!(traces.len(), 2);
+        assert_eq!(traces[0]["path"], "/second");
+        assert_eq!(traces[1]["path"], "/third");
+        assert_eq!(traces[1]["method"], "GET");
+        assert_eq!(traces[1]["status"], 404);
+    }
+
+    #[tokio::test]
+    async fn httptrace_stamps_entries_with_its_clock() {
+        let clock = api::MockClock::new(std::time::UNIX_EPOCH + Duration::from_secs(1_000));
+        let http_trace = api::HttpTrace::new(2).with_clock(Arc::new(clock.clone()));
+        let mut app = http_trace.layer(app()).into_service();
+
+        for _ in 0..2 {
+            let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+            app.ready().await.unwrap().call(request).await.unwrap();
+            clock.advance(Duration::from_secs(60));
+        }
+
+        let stamps = http_trace
+            .entries()
+            .iter()
+            .map(|entry| entry.timestamp_ms)
+            .collect::<Vec<_>>();
+        assert_eq!(stamps, [1_000_000, 1_060_000]);
+    }
+
+    #[derive(Debug)]
+    struct ToggleHealthCheck(Arc<AtomicBool>);
+
+    #[async_trait]
+    impl StateChecker for ToggleHealthCheck {
+        async fn is_ready(&self) -> bool {
+            self.0.load(Ordering::SeqCst)
+        }
+
+        async fn is_alive(&self) -> bool {
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn health_change_notifies_once_per_transition() {
+        let healthy = Arc::new(AtomicBool::new(true));
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+
+        let listener_notifications = notifications.clone();
+        let mut actuator_state =
+            api::ActuatorState::default().with_health_change_listener(move |is_health| {
+                listener_notifications.lock().unwrap().push(is_health)
+            });
+        actuator_state.add_health_checker(
+            "toggle".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(ToggleHealthCheck(
+                healthy.clone(),
+            )))),
+        );
+
+        for _ in 0..3 {
+            actuator_state.check_all_health().await;
+        }
+        assert!(notifications.lock().unwrap().is_empty());
+
+        healthy.store(false, Ordering::SeqCst);
+        for _ in 0..3 {
+            actuator_state.check_all_health().await;
+        }
+        assert_eq!(*notifications.lock().unwrap(), vec![false]);
+
+        healthy.store(true, Ordering::SeqCst);
+        for _ in 0..3 {
+            actuator_state.check_all_health().await;
+        }
+        assert_eq!(*notifications.lock().unwrap(), vec![false, true]);
+    }
+
+    #[tokio::test]
+    async fn state_check_loop_uses_configured_interval() {
+        let healthy = Arc::new(AtomicBool::new(true));
+        let notifications = Arc::new(Mutex::new(Vec::new()));
+
+        let listener_notifications = notifications.clone();
+        let mut actuator_state = api::ActuatorState::new()
+            .with_state_check_interval(Duration::from_millis(50))
+            .with_health_change_listener(move |is_health| {
+                listener_notifications.lock().unwrap().push(is_health)
+            });
+        actuator_state.add_health_checker(
+            "toggle".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(ToggleHealthCheck(
+                healthy.clone(),
+            )))),
+        );
+
+        // Picked up by a scheduled check well before the default 10s
+        healthy.store(false, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(*notifications.lock().unwrap(), vec![false]);
+        assert!(actuator_state.state_check_count() >= 2);
+
+        healthy.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(*notifications.lock().unwrap(), vec![false, true]);
+
+        assert!(actuator_state.shutdown().await);
+    }
+
+    #[tokio::test]
+    async fn readiness_recovers_after_consecutive_passing_checks() {
+        let healthy = Arc::new(AtomicBool::new(true));
+        let mut actuator_state = api::ActuatorState::default().with_ready_recovery_checks(3);
+        actuator_state.add_health_checker(
+            "toggle".to_string(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(ToggleHealthCheck(
+                healthy.clone(),
+            )))),
+        );
+
+        let mut app = ActuatorRouterBuilder::new(app())
+            .with_readiness_route()
+            .with_layer(Some(Extension(actuator_state.clone())))
+            .build()
+            .into_service();
+        let mut readiness = || {
+            let request = Request::builder()
+                .uri("/actuator/health/readiness")
+                .body(Body::empty())
+                .unwrap();
+            let response = app.call(request);
+            async move { response.await.unwrap().status() }
+        };
+        assert_eq!(readiness().await, StatusCode::OK);
+
+        // Down on the first failure
+        healthy.store(false, Ordering::SeqCst);
+        actuator_state.check_all_health().await;
+        assert_eq!(readiness().await, StatusCode::SERVICE_UNAVAILABLE);
+
+        healthy.store(true, Ordering::SeqCst);
+        for _ in 0..2 {
+            actuator_state.check_all_health().await;
+            assert_eq!(readiness().await, StatusCode::SERVICE_UNAVAILABLE);
+        }
+        actuator_state.check_all_health().await;
+        assert_eq!(readiness().await, StatusCode::OK);
+
+        // A failure part way through restarts the count
+        healthy.store(false, Ordering::SeqCst);
+        actuator_state.check_all_health().await;
+        healthy.store(true, Ordering::SeqCst);
+        actuator_state.check_all_health().await;
+        healthy.store(false, Ordering::SeqCst);
+        actuator_state.check_all_health().await;
+        healthy.store(true, Ordering::SeqCst);
+        for _ in 0..2 {
+            actuator_state.check_all_health().await;
+            assert_eq!(readiness().await, StatusCode::SERVICE_UNAVAILABLE);
+        }
+        actuator_state.check_all_health().await;
+        assert_eq!(readiness().await, StatusCode::OK);
+    }
+
+    #[test]
+    fn state_check_delay_is_jittered() {
+        let actuator_state =
+            ActuatorState::default().with_state_check_jitter(Duration::from_millis(500));
+
+        let delays = (0..50)
+            .map(|_| actuator_state.next_state_check_delay())
+            .collect::<Vec<_>>();
+        for delay in &delays {
+            assert!(*delay >= Duration::from_secs(10));
+            assert!(*delay <= Duration::from_millis(10_500));
+        }
+        assert!(delays.windows(2).any(|pair| pair[0] != pair[1]));
+
+        // Without jitter the interval is fixed
+        let actuator_state = ActuatorState::default();
+        assert_eq!(
+            actuator_state.next_state_check_delay(),
+            Duration::from_secs(10)
+        );
+    }
+
     #[tokio::test]
     async fn inject_actuator() {
         let app = app();
@@ -424,7 +2008,7 @@ mod tests {
         // Add health checkers
         actuator_state.add_health_checker(
             "database".to_string(),
-            Arc::new(Mutex::new(Box::new(DatabaseHealthCheck {
+            Arc::new(tokio::sync::Mutex::new(Box::new(DatabaseHealthCheck {
                 ready: true,
                 alive: true,
             }))),
@@ -4,8 +4,20 @@
 //!
 //! - `GET /todos`: return a JSON list of Todos.
 //! - `POST /todos`: create a new Todo.
+//! - `POST /todos/bulk`: create a JSON list of Todos at once.
+//! - `GET /todos/:id`: return a specific Todo.
 //! - `PUT or PATCH /todos/:id`: update a specific Todo.
 //! - `DELETE /todos/:id`: delete a specific Todo.
+//! - `POST /todos/reorder`: set the Todo positions used by `sort_by=position`.
+//! - `POST /todos/:id/archive` and `POST /todos/:id/unarchive`: move a completed Todo to the
+//!   archive and back.
+//! - `GET /todos/archive`: return a JSON list of archived Todos.
+//! - `GET /todos/stats`: return Todo totals and a histogram of completions.
+//! - `POST /rpc`: JSON-RPC 2.0 calls and batches over the Todo operations.
+//! - `GET /version`: return the service version, compiler and commit.
+//! - `GET /auth/:provider` and `GET /auth/:provider/callback`: sign in with `google` or
+//!   `github`, when configured.
+//! - `GET /auth/me`: return the signed in user's session.
 //!
 //! Run with
 //!
@@ -14,7 +26,6 @@
 //! ```
 
 use rest_service_lib as lib;
-use std::net::SocketAddr;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[tokio::main]
@@ -28,17 +39,8 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    // Compose the routes
-    let app = lib::api::app();
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
-        .await
-        .unwrap();
-    tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .await
-    .unwrap();
+    if let Err(err) = lib::run(lib::config::Config::from_env()).await {
+        tracing::error!("{err}");
+        std::process::exit(1);
+    }
 }
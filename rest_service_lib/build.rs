@@ -0,0 +1,9 @@
+fn main() {
+    // The git hash and compiler come from rest_actuator's build script, see `build_info`
+    #[cfg(feature = "grpc")]
+    {
+        // Use the vendored protoc so the build doesn't depend on a system install
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+        tonic_build::compile_protos("proto/todo.proto").unwrap();
+    }
+}
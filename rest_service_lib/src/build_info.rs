@@ -0,0 +1,9 @@
+// Build metadata captured at compile time
+pub const NAME: &str = env!("CARGO_PKG_NAME");
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub use rest_actuator::build_info::{GIT_HASH, RUSTC_VERSION};
+
+// Version string reported to clients, e.g. `0.1.0+1a2b3c4`
+pub fn app_version() -> String {
+    format!("{VERSION}+{GIT_HASH}")
+}
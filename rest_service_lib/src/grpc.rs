@@ -0,0 +1,146 @@
+use crate::api::{self, AppState, StoreError, Todo, UpdateTodo};
+use std::net::SocketAddr;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+pub mod proto {
+    tonic::include_proto!("todo.v1");
+}
+
+use proto::todos_server::{Todos, TodosServer};
+
+// gRPC mirror of the REST todo API, sharing its store
+pub struct TodoService {
+    state: AppState,
+}
+
+impl TodoService {
+    pub(crate) fn new(state: AppState) -> Self {
+        TodoService { state }
+    }
+}
+
+pub(crate) fn server(state: AppState) -> TodosServer<TodoService> {
+    TodosServer::new(TodoService::new(state))
+}
+
+// Serve the gRPC API on its own port until shutdown
+pub(crate) async fn serve(
+    addr: SocketAddr,
+    state: AppState,
+) -> Result<(), tonic::transport::Error> {
+    tracing::debug!("gRPC listening on {addr}");
+    tonic::transport::Server::builder()
+        .add_service(server(state))
+        .serve(addr)
+        .await
+}
+
+fn to_proto(todo: &Todo) -> proto::Todo {
+    proto::Todo {
+        id: todo.id.to_string(),
+        text: todo.text.clone(),
+        completed: todo.completed,
+    }
+}
+
+// `Status` is large, but it's what every handler returns anyway
+#[allow(clippy::result_large_err)]
+fn parse_id(id: &str) -> Result<Uuid, Status> {
+    id.parse()
+        .map_err(|_| Status::invalid_argument(format!("invalid todo id: {id}")))
+}
+
+fn to_status(error: StoreError) -> Status {
+    let message = error.to_string();
+    match error {
+        StoreError::NotFound => Status::not_found(message),
+        StoreError::Conflict => Status::failed_precondition(message),
+        StoreError::Full => Status::resource_exhausted(message),
+        StoreError::EmptyText | StoreError::TextTooLong => Status::invalid_argument(message),
+        StoreError::Backend(_) => Status::unavailable(message),
+        StoreError::Poisoned => Status::internal(message),
+    }
+}
+
+#[tonic::async_trait]
+impl Todos for TodoService {
+    async fn list(
+        &self,
+        request: Request<proto::ListTodosRequest>,
+    ) -> Result<Response<proto::ListTodosResponse>, Status> {
+        let request = request.into_inner();
+        let todos = self.state.db.read().unwrap();
+
+        let todos = todos
+            .values()
+            .skip(request.offset.unwrap_or(0) as usize)
+            .take(request.limit.map_or(usize::MAX, |limit| limit as usize))
+            .map(to_proto)
+            .collect();
+
+        Ok(Response::new(proto::ListTodosResponse { todos }))
+    }
+
+    async fn get(
+        &self,
+        request: Request<proto::GetTodoRequest>,
+    ) -> Result<Response<proto::Todo>, Status> {
+        let id = parse_id(&request.into_inner().id)?;
+
+        let todos = self.state.db.read().unwrap();
+        let todo = todos.get(&id).ok_or(to_status(StoreError::NotFound))?;
+        self.state.access_log.touch(id);
+
+        Ok(Response::new(to_proto(todo)))
+    }
+
+    async fn create(
+        &self,
+        request: Request<proto::CreateTodoRequest>,
+    ) -> Result<Response<proto::Todo>, Status> {
+        let todo =
+            api::insert_todo(&self.state, request.into_inner().text, false).map_err(to_status)?;
+
+        Ok(Response::new(to_proto(&todo)))
+    }
+
+    async fn update(
+        &self,
+        request: Request<proto::UpdateTodoRequest>,
+    ) -> Result<Response<proto::Todo>, Status> {
+        let request = request.into_inner();
+        let id = parse_id(&request.id)?;
+
+        let mut todos = self.state.db.write().unwrap();
+        api::flush_pending_update(&self.state, &mut todos, &id);
+        let todo = todos.get_mut(&id).ok_or(to_status(StoreError::NotFound))?;
+        api::apply_update(
+            &self.state,
+            todo,
+            UpdateTodo {
+                text: request.text,
+                completed: request.completed,
+            },
+        )
+        .map_err(to_status)?;
+        self.state.access_log.touch(id);
+
+        Ok(Response::new(to_proto(todo)))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<proto::DeleteTodoRequest>,
+    ) -> Result<Response<proto::DeleteTodoResponse>, Status> {
+        let id = parse_id(&request.into_inner().id)?;
+
+        let mut todos = self.state.db.write().unwrap();
+        api::flush_pending_update(&self.state, &mut todos, &id);
+        if api::remove_todo(&self.state, &mut todos, &id).is_none() {
+            return Err(to_status(StoreError::NotFound));
+        }
+
+        Ok(Response::new(proto::DeleteTodoResponse {}))
+    }
+}
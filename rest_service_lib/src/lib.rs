@@ -4,8 +4,20 @@
 //!
 //! - `GET /todos`: return a JSON list of Todos.
 //! - `POST /todos`: create a new Todo.
+//! - `POST /todos/bulk`: create a JSON list of Todos at once.
+//! - `GET /todos/:id`: return a specific Todo.
 //! - `PUT or PATCH /todos/:id`: update a specific Todo.
 //! - `DELETE /todos/:id`: delete a specific Todo.
+//! - `POST /todos/reorder`: set the Todo positions used by `sort_by=position`.
+//! - `POST /todos/:id/archive` and `POST /todos/:id/unarchive`: move a completed Todo to the
+//!   archive and back.
+//! - `GET /todos/archive`: return a JSON list of archived Todos.
+//! - `GET /todos/stats`: return Todo totals and a histogram of completions.
+//! - `POST /rpc`: JSON-RPC 2.0 calls and batches over the Todo operations.
+//! - `GET /version`: return the service version, compiler and commit.
+//! - `GET /auth/:provider` and `GET /auth/:provider/callback`: sign in with `google` or
+//!   `github`, when configured.
+//! - `GET /auth/me`: return the signed in user's session.
 //!
 //! Run with
 //!
@@ -13,29 +25,56 @@
 //! cargo run -p rest_service
 //! ```
 
+pub mod build_info;
+pub mod config;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod oauth;
+mod persist;
+mod represent;
+mod rpc;
+
 pub mod api {
     use axum::{
+        async_trait,
+        body::{Bytes, HttpBody},
         error_handling::HandleErrorLayer,
-        extract::{Path, Query, State},
-        http::StatusCode,
-        response::IntoResponse,
-        routing::{get, post, put},
+        extract::{
+            DefaultBodyLimit, FromRef, FromRequest, MatchedPath, Path, Query, Request, State,
+        },
+        http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+        middleware::{self, Next},
+        response::{IntoResponse, Response},
+        routing::{get, post},
         Json, Router,
     };
     use serde::{Deserialize, Serialize};
-    use std::time::Duration;
     use std::{
-        collections::HashMap,
+        collections::{BTreeMap, HashMap},
         sync::{Arc, RwLock},
     };
     use tower::{BoxError, ServiceBuilder};
-    use tower_http::trace::TraceLayer;
+    use tower_http::{set_header::SetResponseHeaderLayer, trace::TraceLayer};
 
+    use crate::build_info;
+    use crate::config::{
+        Config, CorsConfig, EvictionPolicy, FieldNaming, Middleware, TextOverflow,
+    };
+    use crate::oauth;
+    use crate::represent::{Represent, Representation};
     use axum::extract::ConnectInfo;
     use axum::Extension;
-    use rest_actuator::api::{ActuatorRouterBuilder, ActuatorState, StateChecker};
+    // Supports repeated keys such as `?id=a&id=b`
+    use axum_extra::extract::{Query as ExtraQuery, QueryRejection};
+    use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, Utc};
+    use rest_actuator::api::{
+        ActuatorRouterBuilder, ActuatorState, HealthCheckerRegistry, HttpTrace, SharedClock,
+        StateChecker, SystemClock,
+    };
     use std::net::SocketAddr;
-    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::{mpsc, Mutex};
+    use std::time::Duration;
     use utoipa::OpenApi;
     use utoipa::ToSchema;
     use utoipa_swagger_ui::SwaggerUi;
@@ -43,94 +82,775 @@ pub mod api {
 
     #[derive(OpenApi)]
     #[openapi(
-        paths(todos_index, todos_create, todos_update, todos_delete),
-        components(schemas(Pagination, Todo, CreateTodo, UpdateTodo))
+        paths(
+            todos_index,
+            todos_create,
+            todos_bulk_create,
+            todos_get_one,
+            todos_update,
+            todos_patch,
+            todos_delete,
+            todos_reorder,
+            todos_archive_index,
+            todos_archive,
+            todos_unarchive,
+            todos_stats,
+            version
+        ),
+        components(schemas(
+            Pagination,
+            SortBy,
+            SortOrder,
+            TodoView,
+            TodoSummary,
+            Todo,
+            CreateTodo,
+            UpdateTodo,
+            ReorderTodos,
+            StatsQuery,
+            UpdateReturn,
+            StatsBucket,
+            TodoStats,
+            CompletionBucket,
+            VersionInfo
+        ))
     )]
     struct ApiDoc;
 
+    // The generated spec, plus the configured `servers` so generated clients get absolute URLs
+    fn openapi(config: &Config) -> utoipa::openapi::OpenApi {
+        let mut openapi = ApiDoc::openapi();
+        if !config.openapi_servers.is_empty() {
+            openapi.servers = Some(
+                config
+                    .openapi_servers
+                    .iter()
+                    .map(utoipa::openapi::Server::new)
+                    .collect(),
+            );
+        }
+        openapi
+    }
+
+    pub const APP_VERSION_HEADER: &str = "x-app-version";
+    pub const MERGE_PATCH_CONTENT_TYPE: &str = "application/merge-patch+json";
+    pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+    pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
     #[derive(Debug)]
     struct DatabaseHealthCheck {
         ready: bool,
         alive: bool,
     }
 
+    #[async_trait]
     impl StateChecker for DatabaseHealthCheck {
-        fn is_ready(&self) -> bool {
+        async fn is_ready(&self) -> bool {
             self.ready
         }
 
-        fn is_alive(&self) -> bool {
+        async fn is_alive(&self) -> bool {
             self.alive
         }
     }
 
+    // Ready while a remote feature flag/config service answers, so a rollout never serves
+    // with stale or missing config. Probes run in the background and checks read the
+    // last result, keeping the actuator's checks cheap
+    #[derive(Debug)]
+    pub(crate) struct ConfigServiceHealthCheck {
+        reachable: Arc<AtomicBool>,
+    }
+
+    impl ConfigServiceHealthCheck {
+        // Probe `url` every `interval` until the checker is dropped; not ready until the
+        // first probe succeeds
+        pub(crate) fn spawn(url: String, interval: Duration) -> Self {
+            let reachable = Arc::new(AtomicBool::new(false));
+            let probed = Arc::downgrade(&reachable);
+
+            tokio::spawn(async move {
+                let client = reqwest::Client::builder()
+                    .timeout(interval)
+                    .build()
+                    .expect("HTTP client for config service probes");
+                let mut ticks = tokio::time::interval(interval);
+                ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    ticks.tick().await;
+                    let Some(reachable) = probed.upgrade() else {
+                        break;
+                    };
+
+                    let is_reachable = client
+                        .get(&url)
+                        .send()
+                        .await
+                        .is_ok_and(|response| response.status().is_success());
+                    if reachable.swap(is_reachable, Ordering::Relaxed) != is_reachable {
+                        tracing::warn!(url, is_reachable, "config service reachability changed");
+                    }
+                }
+            });
+
+            ConfigServiceHealthCheck { reachable }
+        }
+    }
+
+    #[async_trait]
+    impl StateChecker for ConfigServiceHealthCheck {
+        async fn is_ready(&self) -> bool {
+            self.reachable.load(Ordering::Relaxed)
+        }
+
+        // Missing config is no reason to restart the service
+        async fn is_alive(&self) -> bool {
+            true
+        }
+    }
+
     pub fn app() -> Router {
-        let db = Db::default();
+        app_with_config(Config::default())
+    }
+
+    pub fn app_with_config(config: Config) -> Router {
+        app_with_state(AppState::new(config))
+    }
+
+    // The default app, callable from `allowed_origins` with the default CORS methods and
+    // headers; see `CorsConfig` for a finer grained policy
+    pub fn app_with_cors(allowed_origins: Vec<String>) -> Router {
+        app_with_config(Config {
+            cors: Some(CorsConfig {
+                allowed_origins,
+                ..CorsConfig::default()
+            }),
+            ..Config::default()
+        })
+    }
 
-        let mut actuator_state = ActuatorState::new();
+    // Build the app around an existing state, e.g. one shared with the gRPC server
+    pub(crate) fn app_with_state(state: AppState) -> Router {
+        let actuator_state = actuator_state(&state.config);
+        app_with_actuator(state, actuator_state)
+    }
 
-        // Add health checkers
-        actuator_state.add_health_checker(
-            "database".to_string(),
-            Arc::new(Mutex::new(Box::new(DatabaseHealthCheck {
+    // Actuator state with the service's health checkers; the ones it can't serve without
+    // are critical, so startup can wait for them
+    pub(crate) fn actuator_state(config: &Config) -> ActuatorState {
+        let mut registry = HealthCheckerRegistry::new().register_critical(
+            "database",
+            DatabaseHealthCheck {
                 ready: true,
                 alive: true,
-            }))),
+            },
         );
+        if let Some(url) = &config.config_service_url {
+            registry = registry.register_critical(
+                "config_service",
+                ConfigServiceHealthCheck::spawn(url.clone(), config.config_service_probe_interval),
+            );
+        }
+        ActuatorState::new_with(|state| {
+            state
+                .with_force_ready(config.force_ready)
+                .with_force_live(config.force_live)
+                .with_state_check_interval(config.health_check_interval)
+                .with_state_check_jitter(config.health_check_jitter)
+                .with_skip_first_check(config.health_check_skip_first)
+                .with_ready_recovery_checks(config.health_check_recovery_checks)
+                .with_shutdown_timeout(config.health_check_shutdown_timeout)
+        })
+        .with_health_checkers(registry)
+        .with_info(
+            "build",
+            serde_json::json!({
+                "version": build_info::VERSION,
+                "name": build_info::NAME,
+                "rustc": build_info::RUSTC_VERSION,
+            }),
+        )
+        .with_info("git", serde_json::json!({ "commit": build_info::GIT_HASH }))
+    }
 
+    pub(crate) fn app_with_actuator(state: AppState, actuator_state: ActuatorState) -> Router {
+        let config = state.config.clone();
+
+        let maintenance = actuator_state.clone();
         let extension: Option<Extension<ActuatorState>> = Some(Extension(actuator_state));
+        let http_trace = HttpTrace::new(config.httptrace_capacity).with_clock(state.clock.clone());
+        let clock = state.clock.clone();
 
         let router = ActuatorRouterBuilder::new(Router::new())
             .with_readiness_route()
             .with_liveness_route()
             .with_info_route()
             .with_health_route()
+            .with_drain_route()
+            .with_maintenance_route()
+            .with_httptrace_route(http_trace.clone())
+            .with_metrics_route()
             .with_layer(extension)
             .build();
 
-        // Compose the routes
-        router
+        let todo_routes = Router::new()
             .route("/todos", get(todos_index).post(todos_create))
+            .route("/todos/bulk", post(todos_bulk_create))
+            .route("/todos/stats", get(todos_stats))
+            .route("/todos/reorder", post(todos_reorder))
+            .route("/todos/archive", get(todos_archive_index))
+            .route("/todos/:id/archive", post(todos_archive))
+            .route("/todos/:id/unarchive", post(todos_unarchive))
             .route(
                 "/todos/:id",
-                put(todos_update).patch(todos_update).delete(todos_delete),
+                get(todos_get_one)
+                    .put(todos_update)
+                    .patch(todos_patch)
+                    .delete(todos_delete),
+            );
+        let todo_routes = if config.strict_json {
+            todo_routes.layer(middleware::from_fn(reject_trailing_json))
+        } else {
+            todo_routes
+        };
+        let todo_routes = if config.strict_query {
+            todo_routes.layer(middleware::from_fn(reject_unknown_query_params))
+        } else {
+            todo_routes
+        };
+        let todo_routes = if config.deprecated_fields.is_empty() {
+            todo_routes
+        } else {
+            todo_routes.layer(middleware::from_fn_with_state(
+                Arc::<[String]>::from(config.deprecated_fields.clone()),
+                warn_deprecated_fields,
+            ))
+        };
+        // Business routes, turned away in maintenance mode
+        let business_routes =
+            todo_routes
+                .route("/rpc", post(crate::rpc::rpc_handler))
+                .route(
+                    "/json",
+                    post(|payload: Json<serde_json::Value>| async move {
+                        Json(serde_json::json!({ "data": payload.0 }))
+                    }),
+                )
+                .route(
+                    "/requires-connect-info",
+                    get(|ConnectInfo(addr): ConnectInfo<SocketAddr>| async move {
+                        format!("Hi {addr}")
+                    }),
+                );
+        let business_routes = maintenance.maintenance_layer(business_routes);
+
+        // Compose the routes
+        let router = router
+            .merge(business_routes)
+            .route("/version", get(version))
+            .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", openapi(&config)))
+            .with_state(state);
+        let router = match oauth_routes(&config, clock) {
+            Some(oauth_routes) => router.merge(maintenance.maintenance_layer(oauth_routes)),
+            None => router,
+        };
+
+        // Add middleware to all routes
+        let router =
+            apply_middleware(router, &config).layer(middleware::map_response(empty_content_length));
+        let router = if config.response_headers.is_empty() {
+            router
+        } else {
+            router.layer(middleware::map_response_with_state(
+                Arc::<[(HeaderName, HeaderValue)]>::from(config.response_headers.clone()),
+                set_response_headers,
+            ))
+        };
+
+        // Outermost, so the trace and metrics see the final status of every request
+        rest_actuator::api::metrics_layer(http_trace.layer(router))
+    }
+
+    // Sign in with every provider whose OAuth credentials are both configured
+    fn oauth_routes(config: &Config, clock: SharedClock) -> Option<Router> {
+        let credentials =
+            |id: &Option<String>, secret: &Option<String>| Some((id.clone()?, secret.clone()?));
+        let providers = [
+            credentials(
+                &config.google_oauth_client_id,
+                &config.google_oauth_client_secret,
             )
-            .route(
-                "/json",
-                post(|payload: Json<serde_json::Value>| async move {
-                    Json(serde_json::json!({ "data": payload.0 }))
-                }),
+            .map(|(id, secret)| oauth::OAuthProvider::google(id, secret)),
+            credentials(
+                &config.github_oauth_client_id,
+                &config.github_oauth_client_secret,
             )
-            .route(
-                "/requires-connect-info",
-                get(|ConnectInfo(addr): ConnectInfo<SocketAddr>| async move { format!("Hi {addr}") }),
-            )
-            .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
-            // Add middleware to all routes
-            .layer(
-                ServiceBuilder::new()
-                    .layer(HandleErrorLayer::new(|error: BoxError| async move {
-                        if error.is::<tower::timeout::error::Elapsed>() {
-                            Ok(StatusCode::REQUEST_TIMEOUT)
-                        } else {
-                            Err((
-                                StatusCode::INTERNAL_SERVER_ERROR,
-                                format!("Unhandled internal error: {error}"),
-                            ))
-                        }
-                    }))
-                    .timeout(Duration::from_secs(10))
-                    .layer(TraceLayer::new_for_http())
-                    .into_inner(),
+            .map(|(id, secret)| oauth::OAuthProvider::github(id, secret)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        if providers.is_empty() {
+            return None;
+        }
+
+        let redirect_base_url = config
+            .oauth_redirect_base_url
+            .clone()
+            .unwrap_or_else(|| format!("http://{}", config.bind_addr));
+        match oauth::OAuthState::new(providers, &redirect_base_url) {
+            Ok(state) => Some(oauth::routes(state.with_clock(clock))),
+            Err(err) => {
+                tracing::error!("OAuth sign in disabled, invalid OAUTH_REDIRECT_BASE_URL: {err}");
+                None
+            }
+        }
+    }
+
+    // Wrap the router in the configured middleware, the first entry being the outermost layer
+    pub fn apply_middleware(router: Router, config: &Config) -> Router {
+        config
+            .middleware
+            .iter()
+            .rev()
+            .fold(router, |router, middleware| match middleware {
+                Middleware::AppVersion => router.layer(SetResponseHeaderLayer::overriding(
+                    HeaderName::from_static(APP_VERSION_HEADER),
+                    HeaderValue::from_str(&build_info::app_version()).unwrap(),
+                )),
+                // CORS stays off unless explicitly configured
+                Middleware::Cors => match &config.cors {
+                    Some(cors) => router.layer(cors.layer()),
+                    None => router,
+                },
+                Middleware::Timeout => {
+                    let expose = config.expose_internal_errors;
+                    router.layer(
+                        ServiceBuilder::new()
+                            .layer(HandleErrorLayer::new(move |error| {
+                                handle_middleware_error(error, expose)
+                            }))
+                            .timeout(config.request_timeout),
+                    )
+                }
+                Middleware::Trace => router.layer(TraceLayer::new_for_http()),
+                // Applies to the accumulated body, so chunked uploads are bounded too
+                Middleware::BodyLimit => router
+                    .layer(DefaultBodyLimit::max(config.max_body_bytes))
+                    .layer(middleware::from_fn_with_state(
+                        config.max_body_bytes,
+                        reject_before_body,
+                    )),
+                Middleware::RequireUserAgent => {
+                    router.layer(middleware::from_fn(require_user_agent))
+                }
+            })
+    }
+
+    // Spell out `Content-Length: 0` on empty 204 and 404 responses, which some proxies
+    // expect rather than inferring it from the missing body
+    async fn empty_content_length(mut response: Response) -> Response {
+        let is_empty = response.body().size_hint().exact() == Some(0);
+        if is_empty
+            && matches!(
+                response.status(),
+                StatusCode::NO_CONTENT | StatusCode::NOT_FOUND
             )
-            .with_state(db)
+        {
+            response
+                .headers_mut()
+                .entry(header::CONTENT_LENGTH)
+                .or_insert(HeaderValue::from(0));
+        }
+        response
+    }
+
+    // Set the configured static headers, replacing any the handler set
+    async fn set_response_headers(
+        State(headers): State<Arc<[(HeaderName, HeaderValue)]>>,
+        mut response: Response,
+    ) -> Response {
+        for (name, value) in headers.iter() {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+        response
+    }
+
+    // Turn away clients that don't identify themselves, such as trivial scrapers
+    async fn require_user_agent(request: Request, next: Next) -> Response {
+        let has_user_agent = request
+            .headers()
+            .get(header::USER_AGENT)
+            .is_some_and(|value| !value.as_bytes().trim_ascii().is_empty());
+        if !has_user_agent {
+            return (StatusCode::BAD_REQUEST, "missing User-Agent header").into_response();
+        }
+
+        next.run(request).await
+    }
+
+    // Refuse requests that are bound to fail before reading their body. Hyper only sends
+    // `100 Continue` once the body is polled, so a client waiting on `Expect: 100-continue`
+    // gets the final status straight away instead of uploading first
+    async fn reject_before_body(
+        State(max_body_bytes): State<usize>,
+        request: Request,
+        next: Next,
+    ) -> Response {
+        let headers = request.headers();
+
+        let unmet_expectation = headers
+            .get(header::EXPECT)
+            .is_some_and(|value| !value.as_bytes().eq_ignore_ascii_case(b"100-continue"));
+        if unmet_expectation {
+            return StatusCode::EXPECTATION_FAILED.into_response();
+        }
+
+        let content_length = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<usize>().ok());
+        if content_length.is_some_and(|length| length > max_body_bytes) {
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        }
+
+        next.run(request).await
+    }
+
+    // Query parameters understood by each todo route, see `Pagination`, `UpdateQuery` and
+    // `StatsQuery`
+    fn known_query_params(method: &Method, path: &str) -> &'static [&'static str] {
+        match (method, path) {
+            (&Method::GET, "/todos") => &[
+                "offset",
+                "limit",
+                "id",
+                "completed",
+                "q",
+                "sort_by",
+                "order",
+                "after",
+                "view",
+                "count_only",
+            ],
+            (&Method::PUT, "/todos/:id") => &["return"],
+            (&Method::GET, "/todos/stats") => &["bucket"],
+            _ => &[],
+        }
+    }
+
+    // Refuse requests with query parameters the route doesn't know, naming them; malformed
+    // query strings are left for the extractors to report
+    async fn reject_unknown_query_params(request: Request, next: Next) -> Response {
+        let Some(path) = request.extensions().get::<MatchedPath>() else {
+            return next.run(request).await;
+        };
+        let Ok(Query(params)) = Query::<Vec<(String, String)>>::try_from_uri(request.uri()) else {
+            return next.run(request).await;
+        };
+
+        let known = known_query_params(request.method(), path.as_str());
+        let mut unknown = params
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| !known.contains(&name.as_str()))
+            .map(|name| format!("`{name}`"))
+            .collect::<Vec<_>>();
+        if unknown.is_empty() {
+            return next.run(request).await;
+        }
+
+        unknown.dedup();
+        let expected = if known.is_empty() {
+            "this route takes none".to_string()
+        } else {
+            format!("expected one of {}", known.join(", "))
+        };
+        ApiError::BadRequest(format!(
+            "unknown query parameter {}; {expected}",
+            unknown.join(", ")
+        ))
+        .into_response()
+    }
+
+    // Refuse JSON request bodies with anything but whitespace after the value, e.g.
+    // `{"text":"x"} garbage`; malformed bodies are left for the extractors to report
+    async fn reject_trailing_json(request: Request, next: Next) -> Response {
+        let is_json = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .is_some_and(|value| {
+                value.as_bytes().starts_with(b"application/json")
+                    || value
+                        .as_bytes()
+                        .starts_with(MERGE_PATCH_CONTENT_TYPE.as_bytes())
+            });
+        if !is_json {
+            return next.run(request).await;
+        }
+
+        let (parts, body) = request.into_parts();
+        // Through the extractor, so the body limit still applies
+        let body = match Bytes::from_request(Request::from_parts(parts.clone(), body), &()).await {
+            Ok(body) => body,
+            Err(rejection) => return rejection.into_response(),
+        };
+
+        let mut values =
+            serde_json::Deserializer::from_slice(&body).into_iter::<serde::de::IgnoredAny>();
+        if values.next().is_some_and(|value| value.is_ok()) && values.next().is_some() {
+            return ApiError::BadRequest("unexpected data after the JSON body".to_string())
+                .into_response();
+        }
+
+        next.run(Request::from_parts(parts, body.into())).await
+    }
+
+    // Answer requests using a deprecated body field with a `Warning` header per field, so
+    // clients learn about it while the field keeps working
+    async fn warn_deprecated_fields(
+        State(deprecated_fields): State<Arc<[String]>>,
+        request: Request,
+        next: Next,
+    ) -> Response {
+        let (parts, body) = request.into_parts();
+        // Through the extractor, so the body limit still applies
+        let body = match Bytes::from_request(Request::from_parts(parts.clone(), body), &()).await {
+            Ok(body) => body,
+            Err(rejection) => return rejection.into_response(),
+        };
+
+        let used = match serde_json::from_slice::<serde_json::Value>(&body) {
+            Ok(serde_json::Value::Object(members)) => deprecated_fields
+                .iter()
+                .filter(|field| members.contains_key(field.as_str()))
+                .cloned()
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut response = next.run(Request::from_parts(parts, body.into())).await;
+        for field in used {
+            // 299 is the miscellaneous persistent warning of RFC 7234
+            let warning = format!("299 - \"field `{field}` is deprecated\"");
+            if let Ok(warning) = HeaderValue::from_str(&warning) {
+                response.headers_mut().append(header::WARNING, warning);
+            }
+        }
+        response
+    }
+
+    // A timestamp given as either RFC 3339 or epoch milliseconds, so todos written back by
+    // clients of either `TIMESTAMP_FORMAT` are accepted
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum TimestampInput {
+        Rfc3339(DateTime<Utc>),
+        EpochMillis(i64),
+    }
+
+    impl TimestampInput {
+        fn into_timestamp<E: serde::de::Error>(self) -> Result<DateTime<Utc>, E> {
+            match self {
+                TimestampInput::Rfc3339(timestamp) => Ok(timestamp),
+                TimestampInput::EpochMillis(millis) => DateTime::from_timestamp_millis(millis)
+                    .ok_or_else(|| E::custom(format!("timestamp out of range: {millis}"))),
+            }
+        }
+    }
+
+    fn deserialize_timestamp<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        TimestampInput::deserialize(deserializer)?.into_timestamp()
     }
 
-    // The query parameters for todos index
+    fn deserialize_optional_timestamp<'de, D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Option::<TimestampInput>::deserialize(deserializer)?
+            .map(TimestampInput::into_timestamp)
+            .transpose()
+    }
+
+    // Timeouts become `408`, anything else a `500` shaped like `ApiError::Internal` plus a
+    // correlation id. The error itself is logged under that id, and only sent to the client
+    // with `expose`
+    pub(crate) async fn handle_middleware_error(error: BoxError, expose: bool) -> Response {
+        if error.is::<tower::timeout::error::Elapsed>() {
+            return StatusCode::REQUEST_TIMEOUT.into_response();
+        }
+
+        let correlation_id = Uuid::new_v4();
+        tracing::error!(%correlation_id, "unhandled internal error: {error}");
+        let message = if expose {
+            format!("Unhandled internal error: {error}")
+        } else {
+            ApiError::Internal.to_string()
+        };
+        let body = serde_json::json!({
+            "error": {
+                "code": ApiError::Internal.code(),
+                "message": message,
+                "correlation_id": correlation_id,
+            }
+        });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+    }
+
+    // The query parameters for todos index, see `Pagination::resolve` for their defaults
     #[derive(Debug, Deserialize, Default, ToSchema)]
-    struct Pagination {
+    pub(crate) struct Pagination {
         pub offset: Option<usize>,
         pub limit: Option<usize>,
+        // Repeatable `id=` parameter restricting the listing to these todos
+        #[serde(default)]
+        pub id: Vec<Uuid>,
+        // Only completed todos with `true`, only open ones with `false`
+        pub completed: Option<bool>,
+        // Case-insensitive substring of the todo text
+        pub q: Option<String>,
+        // Defaults to `created_at`; ignored with `after`, which walks position order
+        pub sort_by: Option<SortBy>,
+        #[serde(default)]
+        pub order: SortOrder,
+        // Cursor: list the todos positioned after this one, an alternative to `offset`
+        pub after: Option<Uuid>,
+        #[serde(default)]
+        pub view: TodoView,
+        // Answer with an empty body and only `X-Total-Count`
+        #[serde(default)]
+        pub count_only: bool,
+    }
+
+    // Query parameters that can't be combined; listed once so every new filter is checked
+    const EXCLUSIVE_PARAMS: &[(&str, &str)] = &[("offset", "after")];
+
+    // `Pagination` with its defaults applied and validated
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(crate) struct PaginationResolved {
+        pub offset: usize,
+        pub limit: usize,
+        // Empty for all todos
+        pub id: Vec<Uuid>,
+        pub completed: Option<bool>,
+        // Lowercased for case-insensitive matching
+        pub q: Option<String>,
+        pub sort_by: SortBy,
+        pub order: SortOrder,
+        pub after: Option<Uuid>,
+        pub view: TodoView,
+        pub count_only: bool,
+    }
+
+    impl Pagination {
+        // The one place listing defaults are decided, so every listing resolves alike
+        pub(crate) fn resolve(self) -> Result<PaginationResolved, ApiError> {
+            self.check_exclusive()?;
+
+            // The cursor walks position order
+            let (sort_by, order) = match self.after {
+                Some(_) => (SortBy::Position, SortOrder::Asc),
+                None => (self.sort_by.unwrap_or(SortBy::CreatedAt), self.order),
+            };
+
+            Ok(PaginationResolved {
+                offset: self.offset.unwrap_or(0),
+                limit: self.limit.unwrap_or(usize::MAX),
+                id: self.id,
+                completed: self.completed,
+                q: self.q.as_deref().map(str::to_lowercase),
+                sort_by,
+                order,
+                after: self.after,
+                view: self.view,
+                count_only: self.count_only,
+            })
+        }
+
+        fn is_given(&self, param: &str) -> bool {
+            match param {
+                "offset" => self.offset.is_some(),
+                "after" => self.after.is_some(),
+                _ => false,
+            }
+        }
+
+        fn check_exclusive(&self) -> Result<(), ApiError> {
+            match EXCLUSIVE_PARAMS
+                .iter()
+                .find(|(first, second)| self.is_given(first) && self.is_given(second))
+            {
+                Some((first, second)) => Err(ApiError::BadRequest(format!(
+                    "`{first}` and `{second}` are mutually exclusive"
+                ))),
+                None => Ok(()),
+            }
+        }
+    }
+
+    // Fields the todos index can be sorted by
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub(crate) enum SortBy {
+        // The order set via `POST /todos/reorder`
+        Position,
+        Text,
+        CreatedAt,
+        // Open todos first in ascending order
+        Completed,
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub(crate) enum SortOrder {
+        #[default]
+        Asc,
+        Desc,
+    }
+
+    impl SortBy {
+        // Ties fall back to position, so every listing is deterministic
+        fn compare(self, a: &Todo, b: &Todo) -> std::cmp::Ordering {
+            let by_field = match self {
+                SortBy::Position => std::cmp::Ordering::Equal,
+                SortBy::Text => a.text.cmp(&b.text),
+                SortBy::CreatedAt => a.created_at.cmp(&b.created_at),
+                SortBy::Completed => a.completed.cmp(&b.completed),
+            };
+            by_field.then(a.position.cmp(&b.position))
+        }
+    }
+
+    // Representation of each todo in the index
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub(crate) enum TodoView {
+        #[default]
+        Full,
+        // Just enough for sync clients to tell what changed, see `TodoSummary`
+        Summary,
+    }
+
+    // A todo without its text, as listed by `view=summary`
+    #[derive(Debug, ToSchema)]
+    pub(crate) struct TodoSummary {
+        pub(crate) id: Uuid,
+        pub(crate) completed: bool,
+        pub(crate) completed_at: Option<DateTime<Utc>>,
+        pub(crate) updated_at: DateTime<Utc>,
+    }
+
+    impl From<&Todo> for TodoSummary {
+        fn from(todo: &Todo) -> Self {
+            TodoSummary {
+                id: todo.id,
+                completed: todo.completed,
+                completed_at: todo.completed_at,
+                updated_at: todo.updated_at,
+            }
+        }
     }
 
     /// Get todos
@@ -140,33 +860,146 @@ pub mod api {
     get,
     path = "/todos",
     responses(
-        (status = 200, description = "Todos found successfully", body = [Todo])
+        (status = 200, description = "Todos found successfully, as `TodoSummary` items with `view=summary`", body = [Todo],
+            content_type = ["application/json", "application/msgpack"],
+            headers(("x-total-count" = usize, description = "Number of matching todos before `offset` and `limit` are applied"))),
+        (status = BAD_REQUEST, description = "A query parameter is invalid, mutually exclusive parameters were combined, or the `after` todo was not found")
     ),
     params(
-        ("pagination" = Option<Pagination>, Query, description = "Todo database pagination to retrieve by offset and limit"),
+        ("pagination" = Option<Pagination>, Query, description = "Todo database pagination to retrieve by offset or `after` cursor and limit, optionally restricted to repeated `id` values, by `completed` status or by a `q` text search; `count_only=true` answers with just `X-Total-Count`"),
     )
     )]
     async fn todos_index(
-        pagination: Option<Query<Pagination>>,
+        pagination: Result<ExtraQuery<Pagination>, QueryRejection>,
         State(db): State<Db>,
-    ) -> impl IntoResponse {
+        State(representation): State<Representation>,
+        headers: HeaderMap,
+    ) -> Result<Response, ApiError> {
+        let ExtraQuery(pagination) =
+            pagination.map_err(|rejection| ApiError::BadRequest(rejection.to_string()))?;
+        let pagination = pagination.resolve()?;
+
         let todos = db.read().unwrap();
+        let matches = matching_todos(&todos, &pagination)?;
+
+        // Counted without collecting, sorting or serializing the matches
+        if pagination.count_only {
+            let total = matches.count();
+            return Ok(([(TOTAL_COUNT_HEADER, HeaderValue::from(total))], ()).into_response());
+        }
 
-        let Query(pagination) = pagination.unwrap_or_default();
+        // Lets clients size their pagination without fetching every page
+        let (total, todos) = page_todos(matches, &pagination);
 
-        let todos = todos
+        let mut response = match pagination.view {
+            TodoView::Full => negotiate(&headers, representation, &todos),
+            TodoView::Summary => {
+                let summaries = todos.iter().map(TodoSummary::from).collect::<Vec<_>>();
+                negotiate(&headers, representation, &summaries)
+            }
+        };
+        response
+            .headers_mut()
+            .insert(TOTAL_COUNT_HEADER, HeaderValue::from(total));
+        Ok(response)
+    }
+
+    // The todos passing the filters of `pagination`, in no particular order
+    pub(crate) fn matching_todos<'a>(
+        todos: &'a HashMap<Uuid, Todo>,
+        pagination: &'a PaginationResolved,
+    ) -> Result<impl Iterator<Item = &'a Todo>, ApiError> {
+        let after = match pagination.after {
+            Some(after) => match todos.get(&after) {
+                Some(todo) => Some(todo.position),
+                None => {
+                    return Err(ApiError::BadRequest(format!(
+                        "`after` todo {after} was not found"
+                    )))
+                }
+            },
+            None => None,
+        };
+
+        Ok(todos
             .values()
-            .skip(pagination.offset.unwrap_or(0))
-            .take(pagination.limit.unwrap_or(usize::MAX))
+            .filter(|todo| pagination.id.is_empty() || pagination.id.contains(&todo.id))
+            .filter(|todo| {
+                pagination
+                    .completed
+                    .is_none_or(|completed| todo.completed == completed)
+            })
+            .filter(|todo| {
+                pagination
+                    .q
+                    .as_ref()
+                    .is_none_or(|q| todo.text.to_lowercase().contains(q))
+            })
+            .filter(move |todo| after.is_none_or(|after| todo.position > after)))
+    }
+
+    // Sort `matches` and cut out the requested page, along with the number of matches
+    pub(crate) fn page_todos<'a>(
+        matches: impl Iterator<Item = &'a Todo>,
+        pagination: &PaginationResolved,
+    ) -> (usize, Vec<Todo>) {
+        let mut todos = matches.collect::<Vec<_>>();
+        todos.sort_by(|a, b| match pagination.order {
+            SortOrder::Asc => pagination.sort_by.compare(a, b),
+            SortOrder::Desc => pagination.sort_by.compare(b, a),
+        });
+
+        let total = todos.len();
+        let todos = todos
+            .into_iter()
+            .skip(pagination.offset)
+            .take(pagination.limit)
             .cloned()
-            .collect::<Vec<_>>();
+            .collect();
+        (total, todos)
+    }
 
-        Json(todos)
+    // Serialize as MessagePack when the client accepts it, JSON otherwise
+    fn negotiate<T: Represent>(
+        headers: &HeaderMap,
+        representation: Representation,
+        value: &T,
+    ) -> Response {
+        let value = value.represent(representation);
+        let accepts_msgpack = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.contains(MSGPACK_CONTENT_TYPE));
+
+        if !accepts_msgpack {
+            return Json(value).into_response();
+        }
+
+        match rmp_serde::to_vec_named(&value) {
+            Ok(body) => ([(header::CONTENT_TYPE, MSGPACK_CONTENT_TYPE)], body).into_response(),
+            Err(err) => {
+                tracing::error!("failed to serialize msgpack response: {err}");
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
     }
 
-    #[derive(Debug, Deserialize, ToSchema)]
+    #[derive(Deserialize, ToSchema)]
     struct CreateTodo {
         text: String,
+        // Lets imports create todos that are already done
+        #[serde(default)]
+        completed: bool,
+    }
+
+    impl std::fmt::Debug for CreateTodo {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("CreateTodo")
+                .field("text", &LoggedText(&self.text))
+                .field("completed", &self.completed)
+                .finish()
+        }
     }
 
     /// Create todo
@@ -176,125 +1009,3745 @@ pub mod api {
     post,
     path = "/todos",
     responses(
-        (status = 201, description = "Create todo successfully", body = Todo)
+        (status = 201, description = "Create todo successfully", body = Todo),
+        (status = UNPROCESSABLE_ENTITY, description = "Todo text is too long"),
+        (status = INSUFFICIENT_STORAGE, description = "Todo store is full")
     )
     )]
     async fn todos_create(
-        State(db): State<Db>,
+        State(state): State<AppState>,
         Json(input): Json<CreateTodo>,
-    ) -> impl IntoResponse {
-        let todo = Todo {
-            id: Uuid::new_v4(),
-            text: input.text,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let todo = insert_todo(&state, input.text, input.completed)?;
+
+        Ok((StatusCode::CREATED, state.represent(&todo)))
+    }
+
+    /// Create todos in bulk
+    ///
+    /// Create a list of todos in database, in the given order. Nothing is created
+    /// when any of them is invalid or they don't all fit in the store
+    #[utoipa::path(
+    post,
+    path = "/todos/bulk",
+    request_body = [CreateTodo],
+    responses(
+        (status = 201, description = "Todos created successfully", body = [Todo]),
+        (status = BAD_REQUEST, description = "A todo is invalid, the error message names its index"),
+        (status = PAYLOAD_TOO_LARGE, description = "More todos than `MAX_BATCH_SIZE`"),
+        (status = INSUFFICIENT_STORAGE, description = "Todo store is full")
+    )
+    )]
+    async fn todos_bulk_create(
+        State(state): State<AppState>,
+        Json(inputs): Json<Vec<CreateTodo>>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        if let Some(max) = state.config.max_batch_size {
+            if inputs.len() > max {
+                return Err(ApiError::PayloadTooLarge(format!(
+                    "at most {max} todos can be created at once"
+                )));
+            }
+        }
+
+        let new_todos = inputs
+            .into_iter()
+            .enumerate()
+            .map(|(index, input)| {
+                new_todo(&state, input.text, input.completed)
+                    .map_err(|err| ApiError::BadRequest(format!("todo {index}: {err}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // One lock for the whole batch, checking for room up front so it's all or nothing
+        let mut todos = state.db.write()?;
+        if let Some(max_todos) = state.config.max_todos {
+            let room = match state.config.eviction_policy {
+                EvictionPolicy::Lru => max_todos,
+                EvictionPolicy::Reject => max_todos.saturating_sub(todos.len()),
+            };
+            if new_todos.len() > room {
+                return Err(ApiError::InsufficientStorage);
+            }
+        }
+
+        let created = new_todos
+            .into_iter()
+            .map(|todo| store_todo(&state, &mut todos, todo))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((StatusCode::CREATED, state.represent(&created)))
+    }
+
+    // Failures of the store helpers, mapped to a status through `ApiError`
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub(crate) enum StoreError {
+        #[error("todo was not found")]
+        NotFound,
+        #[error("todo conflicts with its current state")]
+        Conflict,
+        #[error("todo store is full")]
+        Full,
+        #[error("text must not be empty")]
+        EmptyText,
+        #[error("todo text is too long")]
+        TextTooLong,
+        #[error("todo store backend failed: {0}")]
+        Backend(String),
+        #[error("todo store lock was poisoned")]
+        Poisoned,
+    }
+
+    impl<T> From<std::sync::PoisonError<T>> for StoreError {
+        fn from(_: std::sync::PoisonError<T>) -> Self {
+            StoreError::Poisoned
+        }
+    }
+
+    // Errors returned by the handlers, rendered as
+    // `{"error": {"code": "not_found", "message": "..."}}` with the matching status
+    #[derive(Debug, PartialEq, Eq, thiserror::Error)]
+    pub(crate) enum ApiError {
+        #[error("todo was not found")]
+        NotFound,
+        #[error("todo conflicts with its current state")]
+        Conflict,
+        #[error("{0}")]
+        BadRequest(String),
+        #[error("sign in required")]
+        Unauthorized,
+        #[error("{0}")]
+        UnprocessableEntity(String),
+        #[error("todo has changed since `If-Match` was computed")]
+        PreconditionFailed,
+        #[error("{0}")]
+        PayloadTooLarge(String),
+        #[error("todo store is full")]
+        InsufficientStorage,
+        // Details are logged, not sent to the client
+        #[error("internal error")]
+        Internal,
+    }
+
+    impl ApiError {
+        pub(crate) fn status(&self) -> StatusCode {
+            match self {
+                ApiError::NotFound => StatusCode::NOT_FOUND,
+                ApiError::Conflict => StatusCode::CONFLICT,
+                ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+                ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+                ApiError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                ApiError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+                ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+                ApiError::InsufficientStorage => StatusCode::INSUFFICIENT_STORAGE,
+                ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
+
+        fn code(&self) -> &'static str {
+            match self {
+                ApiError::NotFound => "not_found",
+                ApiError::Conflict => "conflict",
+                ApiError::BadRequest(_) => "bad_request",
+                ApiError::Unauthorized => "unauthorized",
+                ApiError::UnprocessableEntity(_) => "unprocessable_entity",
+                ApiError::PreconditionFailed => "precondition_failed",
+                ApiError::PayloadTooLarge(_) => "payload_too_large",
+                ApiError::InsufficientStorage => "insufficient_storage",
+                ApiError::Internal => "internal",
+            }
+        }
+    }
+
+    impl IntoResponse for ApiError {
+        fn into_response(self) -> Response {
+            let body = serde_json::json!({
+                "error": { "code": self.code(), "message": self.to_string() }
+            });
+            (self.status(), Json(body)).into_response()
+        }
+    }
+
+    impl From<StoreError> for ApiError {
+        fn from(error: StoreError) -> Self {
+            match error {
+                StoreError::NotFound => ApiError::NotFound,
+                StoreError::Conflict => ApiError::Conflict,
+                StoreError::Full => ApiError::InsufficientStorage,
+                StoreError::EmptyText => ApiError::BadRequest(error.to_string()),
+                StoreError::TextTooLong => ApiError::UnprocessableEntity(error.to_string()),
+                StoreError::Backend(_) | StoreError::Poisoned => {
+                    tracing::error!("todo store failed: {error}");
+                    ApiError::Internal
+                }
+            }
+        }
+    }
+
+    impl<T> From<std::sync::PoisonError<T>> for ApiError {
+        fn from(error: std::sync::PoisonError<T>) -> Self {
+            StoreError::from(error).into()
+        }
+    }
+
+    // Store a new todo, making room according to the eviction policy when full
+    pub(crate) fn insert_todo(
+        state: &AppState,
+        text: String,
+        completed: bool,
+    ) -> Result<Todo, StoreError> {
+        let todo = new_todo(state, text, completed)?;
+
+        let mut todos = state.db.write()?;
+        store_todo(state, &mut todos, todo)
+    }
+
+    // Validate the text of a todo about to be created and fill in the server managed fields
+    fn new_todo(state: &AppState, text: String, completed: bool) -> Result<Todo, StoreError> {
+        // Guards against blank rows from misbehaving clients
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(StoreError::EmptyText);
+        }
+
+        let now = state.now();
+        let mut todo = Todo {
+            id: state.ids.next(),
+            text: fit_text(&state.config, text.to_string())?,
             completed: false,
+            completed_at: None,
+            created_at: now,
+            updated_at: now,
+            position: 0,
         };
+        set_completed(&mut todo, completed, now);
+
+        Ok(todo)
+    }
+
+    // Add a todo to the active store as its last position, along with its bookkeeping
+    fn store_todo(
+        state: &AppState,
+        todos: &mut HashMap<Uuid, Todo>,
+        mut todo: Todo,
+    ) -> Result<Todo, StoreError> {
+        let config = &state.config;
+        if let Some(max_todos) = config.max_todos {
+            while todos.len() >= max_todos {
+                let evicted = match config.eviction_policy {
+                    EvictionPolicy::Lru => state.access_log.least_recent(),
+                    EvictionPolicy::Reject => None,
+                };
+                let Some(evicted) = evicted else {
+                    return Err(StoreError::Full);
+                };
+                remove_todo(state, todos, &evicted);
+            }
+        }
 
-        db.write().unwrap().insert(todo.id, todo.clone());
+        todo.position = state.next_position.fetch_add(1, Ordering::Relaxed);
+        todos.insert(todo.id, todo.clone());
+        state.access_log.touch(todo.id);
+        state.counters.add(&todo);
+        tracing::debug!(?todo, "stored todo");
 
-        (StatusCode::CREATED, Json(todo))
+        Ok(todo)
     }
 
-    #[derive(Debug, Deserialize, ToSchema)]
-    struct UpdateTodo {
-        text: Option<String>,
-        completed: Option<bool>,
+    // Remove a todo from the store it was looked up in, along with its bookkeeping
+    pub(crate) fn remove_todo(
+        state: &AppState,
+        todos: &mut HashMap<Uuid, Todo>,
+        id: &Uuid,
+    ) -> Option<Todo> {
+        let todo = todos.remove(id)?;
+        state.access_log.forget(id);
+        state.counters.remove(&todo);
+        Some(todo)
     }
 
-    /// Update todo by id
+    /// Get todo by id
     ///
-    /// Update todo in database by todo id
+    /// Get todo from database by todo id
     #[utoipa::path(
-    put,
+    get,
     path = "/todos/{id}",
     responses(
-        (status = 200, description = "Todo updated successfully", body = Todo),
+        (status = 200, description = "Todo found successfully", body = Todo,
+            headers(("etag" = String, description = "ETag of the todo, for `If-Match`"))),
         (status = NOT_FOUND, description = "Todo was not found")
     ),
     params(
-        ("id" = Path<Uuid>, Path, description = "Todo database id to update Todo for"),
+        ("id" = Path<Uuid>, Path, description = "Todo database id to get Todo for"),
     )
     )]
-    async fn todos_update(
+    async fn todos_get_one(
         Path(id): Path<Uuid>,
         State(db): State<Db>,
-        Json(input): Json<UpdateTodo>,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        let mut todo = db
-            .read()
-            .unwrap()
-            .get(&id)
-            .cloned()
-            .ok_or(StatusCode::NOT_FOUND)?;
+        State(access_log): State<AccessLog>,
+        State(representation): State<Representation>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let todos = db.read()?;
+        let todo = todos.get(&id).ok_or(ApiError::NotFound)?;
+        access_log.touch(id);
 
-        if let Some(text) = input.text {
-            todo.text = text;
+        Ok((
+            [(header::ETAG, etag(todo))],
+            Json(todo.represent(representation)),
+        ))
+    }
+
+    // Strong ETag of a todo's current state: a SHA-256 of its JSON, so it changes with any
+    // of its fields but not across restarts or releases. It identifies the todo rather than
+    // one encoding of it, which is as precise as `If-Match` needs
+    pub(crate) fn etag(todo: &Todo) -> String {
+        use sha2::{Digest, Sha256};
+
+        let digest = Sha256::digest(serde_json::to_vec(todo).unwrap());
+        // 128 bits are plenty to tell the versions of one todo apart
+        let hex = digest[..16]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        format!("\"{hex}\"")
+    }
+
+    // Refuse to change or delete a todo that was modified since the client computed `If-Match`.
+    // `If-Match` uses strong comparison (RFC 9110, section 13.1.1), so weak `W/` tags never match
+    fn check_if_match(headers: &HeaderMap, todo: &Todo) -> Result<(), ApiError> {
+        let Some(if_match) = headers.get(header::IF_MATCH) else {
+            return Ok(());
+        };
+
+        let current = etag(todo);
+        let matches = if_match.to_str().is_ok_and(|if_match| {
+            if_match
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag == current)
+        });
+        if matches {
+            Ok(())
+        } else {
+            Err(ApiError::PreconditionFailed)
+        }
+    }
+
+    #[derive(Clone, Deserialize, ToSchema)]
+    pub(crate) struct UpdateTodo {
+        pub(crate) text: Option<String>,
+        pub(crate) completed: Option<bool>,
+    }
+
+    impl std::fmt::Debug for UpdateTodo {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("UpdateTodo")
+                .field("text", &self.text.as_deref().map(LoggedText))
+                .field("completed", &self.completed)
+                .finish()
         }
+    }
+
+    // What a successful update answers with
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+    #[serde(rename_all = "lowercase")]
+    pub(crate) enum UpdateReturn {
+        // The whole updated todo
+        #[default]
+        Todo,
+        // Only the fields that changed, see `todo_diff`
+        Diff,
+    }
+
+    // The query parameters for todo updates
+    #[derive(Debug, Deserialize, Default)]
+    struct UpdateQuery {
+        #[serde(default, rename = "return")]
+        return_: UpdateReturn,
+    }
+
+    /// Update todo by id
+    ///
+    /// Update todo in database by todo id
+    #[utoipa::path(
+    put,
+    path = "/todos/{id}",
+    responses(
+        (status = 200, description = "Todo updated successfully; with `return=diff`, `{\"id\", \"changes\": {field: {\"old\", \"new\"}}}` instead", body = Todo,
+            headers(("etag" = String, description = "ETag of the updated todo"))),
+        (status = ACCEPTED, description = "Update queued under `UPDATE_DEBOUNCE_MS`; the body is the todo as it will be once applied", body = Todo,
+            headers(("etag" = String, description = "ETag of the todo once the update is applied"))),
+        (status = NOT_FOUND, description = "Todo was not found"),
+        (status = PRECONDITION_FAILED, description = "Todo no longer matches `If-Match`"),
+        (status = UNPROCESSABLE_ENTITY, description = "Todo text is too long")
+    ),
+    params(
+        ("id" = Path<Uuid>, Path, description = "Todo database id to update Todo for"),
+        ("If-Match" = Option<String>, Header, description = "Only update the todo while it still has one of these ETags"),
+        ("return" = Option<UpdateReturn>, Query, description = "`todo` (default) answers with the updated todo, `diff` with only the fields that changed"),
+    )
+    )]
+    async fn todos_update(
+        Path(id): Path<Uuid>,
+        State(state): State<AppState>,
+        Query(query): Query<UpdateQuery>,
+        headers: HeaderMap,
+        Json(input): Json<UpdateTodo>,
+    ) -> Result<Response, ApiError> {
+        if let Some(window) = state.config.update_debounce {
+            return debounce_update(&state, id, query.return_, &headers, input, window);
+        }
+
+        // Hold the write lock across lookup and update so a racing delete can't be undone
+        let mut todos = state.db.write()?;
+        let todo = todos.get_mut(&id).ok_or(ApiError::NotFound)?;
+
+        check_if_match(&headers, todo)?;
+        let before = todo.clone();
+        apply_update(&state, todo, input)?;
+        state.access_log.touch(todo.id);
+
+        Ok(update_response(
+            StatusCode::OK,
+            state.representation(),
+            query.return_,
+            &before,
+            todo,
+        ))
+    }
+
+    fn update_response(
+        status: StatusCode,
+        representation: Representation,
+        return_: UpdateReturn,
+        before: &Todo,
+        after: &Todo,
+    ) -> Response {
+        let body = match return_ {
+            UpdateReturn::Todo => Json(after.represent(representation)).into_response(),
+            UpdateReturn::Diff => Json(serde_json::json!({
+                "id": after.id,
+                "changes": todo_diff(before, after, representation),
+            }))
+            .into_response(),
+        };
+        (status, [(header::ETAG, etag(after))], body).into_response()
+    }
+
+    // Queue `input` instead of applying it, merged into any update of the same todo queued
+    // in the last `window`, and apply the merged update once the window is over. Intermediate
+    // states are never stored, e.g. a todo completed and reopened within the window is never
+    // counted as completed. Answers `202` with the todo as it will be once applied
+    fn debounce_update(
+        state: &AppState,
+        id: Uuid,
+        return_: UpdateReturn,
+        headers: &HeaderMap,
+        input: UpdateTodo,
+        window: Duration,
+    ) -> Result<Response, ApiError> {
+        let input = UpdateTodo {
+            text: input
+                .text
+                .map(|text| fit_text(&state.config, text))
+                .transpose()?,
+            completed: input.completed,
+        };
+
+        // Lock order matches `flush_pending_update`: store first, then the queue
+        let todos = state.db.read()?;
+        let todo = todos.get(&id).ok_or(ApiError::NotFound)?;
+        let mut pending = state.pending_updates.lock().unwrap();
+
+        // `If-Match` refers to the todo as the client last saw it, queued updates included
+        let queued = pending.get(&id);
+        let before = queued.map_or_else(|| todo.clone(), |queued| queued.project(todo));
+        check_if_match(headers, &before)?;
+
+        let update = PendingUpdate {
+            input: match queued {
+                Some(queued) => UpdateTodo {
+                    text: input.text.or(queued.input.text.clone()),
+                    completed: input.completed.or(queued.input.completed),
+                },
+                None => input,
+            },
+            // Stamped once, so the ETag answered now is the one of the todo eventually
+            // stored, unless another write lands first
+            queued_at: state.now(),
+        };
+        let after = update.project(todo);
+        if pending.insert(id, update).is_none() {
+            let state = state.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(window).await;
+                apply_pending_update(&state, id);
+            });
+        }
+        state.access_log.touch(id);
+
+        Ok(update_response(
+            StatusCode::ACCEPTED,
+            state.representation(),
+            return_,
+            todo,
+            &after,
+        ))
+    }
+
+    // The fields queued by `debounce_update` for a todo, merged, and when the last of them
+    // was queued
+    #[derive(Debug, Clone)]
+    pub(crate) struct PendingUpdate {
+        input: UpdateTodo,
+        queued_at: DateTime<Utc>,
+    }
+
+    impl PendingUpdate {
+        // `todo` as it will be once this update is applied
+        fn project(&self, todo: &Todo) -> Todo {
+            let mut projected = todo.clone();
+            apply_fields(&mut projected, self.input.clone(), self.queued_at);
+            projected
+        }
+    }
+
+    // Apply the update queued by `debounce_update` once its window is over
+    fn apply_pending_update(state: &AppState, id: Uuid) {
+        match state.db.write() {
+            Ok(mut todos) => flush_pending_update(state, &mut todos, &id),
+            Err(err) => tracing::error!(
+                "failed to apply debounced update of todo {id}: {}",
+                StoreError::from(err)
+            ),
+        }
+    }
+
+    // Apply the update queued for `id`, if any, to the todo as it is now. Writes that skip
+    // the queue call this first, so the queued fields land before them rather than over them
+    pub(crate) fn flush_pending_update(
+        state: &AppState,
+        todos: &mut HashMap<Uuid, Todo>,
+        id: &Uuid,
+    ) {
+        let Some(queued) = state.pending_updates.lock().unwrap().remove(id) else {
+            return;
+        };
+        // Deleted meanwhile
+        let Some(todo) = todos.get_mut(id) else {
+            return;
+        };
+        if let Err(err) = apply_update_at(state, todo, queued.input, queued.queued_at) {
+            tracing::error!("failed to apply debounced update of todo {id}: {err}");
+        }
+    }
+
+    // Timestamps the server maintains; they follow from the other changes, so a diff leaves
+    // them out
+    const DERIVED_FIELDS: [&str; 2] = ["completed_at", "updated_at"];
+
+    // `{field: {"old": .., "new": ..}}` for every field that differs between the two, with
+    // values spelled as `representation` says. Fields are never renamed: the only ones with an
+    // underscore are derived
+    pub(crate) fn todo_diff(
+        old: &Todo,
+        new: &Todo,
+        representation: Representation,
+    ) -> serde_json::Map<String, serde_json::Value> {
+        let representation = Representation {
+            field_naming: FieldNaming::SnakeCase,
+            ..representation
+        };
+        let serde_json::Value::Object(old) = serde_json::json!(old.represent(representation))
+        else {
+            unreachable!("todos serialize to objects")
+        };
+        let serde_json::Value::Object(mut new) = serde_json::json!(new.represent(representation))
+        else {
+            unreachable!("todos serialize to objects")
+        };
+        old.into_iter()
+            .filter(|(field, _)| !DERIVED_FIELDS.contains(&field.as_str()))
+            .filter_map(|(field, old)| {
+                let new = new.remove(&field)?;
+                (old != new).then(|| (field, serde_json::json!({ "old": old, "new": new })))
+            })
+            .collect()
+    }
+
+    pub(crate) fn apply_update(
+        state: &AppState,
+        todo: &mut Todo,
+        input: UpdateTodo,
+    ) -> Result<(), StoreError> {
+        apply_update_at(state, todo, input, state.now())
+    }
+
+    fn apply_update_at(
+        state: &AppState,
+        todo: &mut Todo,
+        input: UpdateTodo,
+        now: DateTime<Utc>,
+    ) -> Result<(), StoreError> {
+        let text = input
+            .text
+            .map(|text| fit_text(&state.config, text))
+            .transpose()?;
+
+        state.counters.remove(todo);
+        apply_fields(
+            todo,
+            UpdateTodo {
+                text,
+                completed: input.completed,
+            },
+            now,
+        );
+        state.counters.add(todo);
+        tracing::debug!(?todo, "updated todo");
+
+        Ok(())
+    }
+
+    // Set the given fields as of `now`, leaving validation and bookkeeping to the caller
+    fn apply_fields(todo: &mut Todo, input: UpdateTodo, now: DateTime<Utc>) {
+        if let Some(text) = input.text {
+            todo.text = text;
+        }
+        if let Some(completed) = input.completed {
+            set_completed(todo, completed, now);
+        }
+        todo.updated_at = now;
+    }
+
+    // Enforce `max_text_len`, truncating or rejecting according to `text_overflow`
+    fn fit_text(config: &Config, mut text: String) -> Result<String, StoreError> {
+        let Some(max_text_len) = config.max_text_len else {
+            return Ok(text);
+        };
+
+        // Length is counted in characters so truncation never splits one
+        match text.char_indices().nth(max_text_len) {
+            None => Ok(text),
+            Some(_) if config.text_overflow == TextOverflow::Reject => Err(StoreError::TextTooLong),
+            Some((end, _)) => {
+                text.truncate(end);
+                Ok(text)
+            }
+        }
+    }
+
+    // Record when a todo becomes completed, and forget it when reopened
+    fn set_completed(todo: &mut Todo, completed: bool, now: DateTime<Utc>) {
+        if completed && !todo.completed {
+            todo.completed_at = Some(now);
+        } else if !completed {
+            todo.completed_at = None;
+        }
+        todo.completed = completed;
+    }
+
+    // Body of a PATCH request: a partial update or an RFC 7396 merge patch
+    enum PatchTodo {
+        Update(UpdateTodo),
+        MergePatch(serde_json::Value),
+    }
+
+    #[async_trait]
+    impl<S: Send + Sync> FromRequest<S> for PatchTodo {
+        type Rejection = Response;
+
+        async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+            let is_merge_patch = req
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.starts_with(MERGE_PATCH_CONTENT_TYPE));
+
+            if is_merge_patch {
+                let body = Bytes::from_request(req, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                let patch = serde_json::from_slice(&body).map_err(|_| {
+                    ApiError::BadRequest("merge patch is not valid JSON".to_string())
+                        .into_response()
+                })?;
+                Ok(PatchTodo::MergePatch(patch))
+            } else {
+                let Json(input) = Json::<UpdateTodo>::from_request(req, state)
+                    .await
+                    .map_err(IntoResponse::into_response)?;
+                Ok(PatchTodo::Update(input))
+            }
+        }
+    }
+
+    /// Patch todo by id
+    ///
+    /// Partially update todo in database by todo id, accepting either a JSON
+    /// update or an RFC 7396 merge patch (`application/merge-patch+json`)
+    #[utoipa::path(
+    patch,
+    path = "/todos/{id}",
+    request_body(content = UpdateTodo, content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "Todo patched successfully", body = Todo,
+            headers(("etag" = String, description = "ETag of the patched todo"))),
+        (status = NOT_FOUND, description = "Todo was not found"),
+        (status = PRECONDITION_FAILED, description = "Todo no longer matches `If-Match`"),
+        (status = UNPROCESSABLE_ENTITY, description = "Merge patch produced an invalid Todo, or text is too long")
+    ),
+    params(
+        ("id" = Path<Uuid>, Path, description = "Todo database id to patch Todo for"),
+        ("If-Match" = Option<String>, Header, description = "Only patch the todo while it still has one of these ETags"),
+    )
+    )]
+    async fn todos_patch(
+        Path(id): Path<Uuid>,
+        State(state): State<AppState>,
+        headers: HeaderMap,
+        patch: PatchTodo,
+    ) -> Result<impl IntoResponse, ApiError> {
+        // Hold the write lock across lookup and update so a racing delete can't be undone
+        let mut todos = state.db.write()?;
+        flush_pending_update(&state, &mut todos, &id);
+        let todo = todos.get_mut(&id).ok_or(ApiError::NotFound)?;
+
+        check_if_match(&headers, todo)?;
+
+        match patch {
+            PatchTodo::Update(input) => apply_update(&state, todo, input)?,
+            PatchTodo::MergePatch(patch) => {
+                let mut target = serde_json::to_value(&*todo).unwrap();
+                merge_patch(&mut target, &patch);
+
+                // Required fields cleared with null, or a changed id, leave no valid Todo
+                let patched = serde_json::from_value::<Todo>(target)
+                    .ok()
+                    .filter(|patched| patched.id == id)
+                    .ok_or_else(|| {
+                        ApiError::UnprocessableEntity(
+                            "merge patch does not leave a valid todo".to_string(),
+                        )
+                    })?;
+
+                let text = fit_text(&state.config, patched.text)?;
+
+                // Timestamps are server managed, `completed_at` follows `completed`
+                state.counters.remove(todo);
+                todo.text = text;
+                let now = state.now();
+                set_completed(todo, patched.completed, now);
+                todo.updated_at = now;
+                state.counters.add(todo);
+            }
+        }
+
+        state.access_log.touch(todo.id);
+
+        Ok(([(header::ETAG, etag(todo))], state.represent(todo)))
+    }
+
+    // Apply an RFC 7396 merge patch: null removes a member, absent members are left as is
+    fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+        let serde_json::Value::Object(patch) = patch else {
+            *target = patch.clone();
+            return;
+        };
+
+        if !target.is_object() {
+            *target = serde_json::Value::Object(serde_json::Map::new());
+        }
+
+        let target = target.as_object_mut().unwrap();
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(
+                    target.entry(key.clone()).or_insert(serde_json::Value::Null),
+                    value,
+                );
+            }
+        }
+    }
+
+    /// Delete todo by id
+    ///
+    /// Delete todo from database by todo id
+    #[utoipa::path(
+    delete,
+    path = "/todos/{id}",
+    responses(
+        (status = NO_CONTENT, description = "Todo deleted successfully"),
+        (status = NOT_FOUND, description = "Todo was not found"),
+        (status = PRECONDITION_FAILED, description = "Todo no longer matches `If-Match`")
+    ),
+    params(
+        ("id" = Path<Uuid>, Path, description = "Todo database id to delete Todo for"),
+        ("If-Match" = Option<String>, Header, description = "Only delete the todo while it still has one of these ETags"),
+    )
+    )]
+    async fn todos_delete(
+        Path(id): Path<Uuid>,
+        State(state): State<AppState>,
+        headers: HeaderMap,
+    ) -> Result<impl IntoResponse, ApiError> {
+        // Checked under the write lock, like updates, so the todo can't change in between
+        let mut todos = state.db.write()?;
+        flush_pending_update(&state, &mut todos, &id);
+        check_if_match(&headers, todos.get(&id).ok_or(ApiError::NotFound)?)?;
+        remove_todo(&state, &mut todos, &id).ok_or(ApiError::NotFound)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    #[derive(Debug, Deserialize, ToSchema)]
+    struct ReorderTodos {
+        // Todo ids in their new order
+        ids: Vec<Uuid>,
+    }
+
+    /// Reorder todos
+    ///
+    /// Persist the given order as todo positions, as shown by `sort_by=position`.
+    /// A subset of todos may be given; they are reordered among the positions they
+    /// already hold, leaving the other todos in place
+    #[utoipa::path(
+    post,
+    path = "/todos/reorder",
+    request_body = ReorderTodos,
+    responses(
+        (status = NO_CONTENT, description = "Todos reordered successfully"),
+        (status = NOT_FOUND, description = "A todo was not found"),
+        (status = UNPROCESSABLE_ENTITY, description = "The same todo was given more than once")
+    )
+    )]
+    async fn todos_reorder(
+        State(db): State<Db>,
+        Json(input): Json<ReorderTodos>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let mut todos = db.write()?;
+
+        let mut positions = Vec::with_capacity(input.ids.len());
+        for id in &input.ids {
+            let todo = todos.get(id).ok_or(ApiError::NotFound)?;
+            positions.push(todo.position);
+        }
+
+        let mut unique_ids = input.ids.clone();
+        unique_ids.sort();
+        unique_ids.dedup();
+        if unique_ids.len() != input.ids.len() {
+            return Err(ApiError::UnprocessableEntity(
+                "the same todo was given more than once".to_string(),
+            ));
+        }
+
+        positions.sort();
+        for (id, position) in input.ids.iter().zip(positions) {
+            todos.get_mut(id).unwrap().position = position;
+        }
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    /// Get archived todos
+    ///
+    /// Get archived todos, in the order they were archived
+    #[utoipa::path(
+    get,
+    path = "/todos/archive",
+    responses(
+        (status = 200, description = "Archived todos found successfully", body = [Todo])
+    )
+    )]
+    async fn todos_archive_index(
+        State(state): State<AppState>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let mut todos = state.archive.read()?.values().cloned().collect::<Vec<_>>();
+        todos.sort_by_key(|todo| todo.position);
+
+        Ok(state.represent(&todos))
+    }
+
+    /// Archive todo by id
+    ///
+    /// Move a completed todo out of the active list into the archive
+    #[utoipa::path(
+    post,
+    path = "/todos/{id}/archive",
+    responses(
+        (status = 200, description = "Todo archived successfully", body = Todo),
+        (status = NOT_FOUND, description = "Todo was not found"),
+        (status = CONFLICT, description = "Todo is not completed")
+    ),
+    params(
+        ("id" = Path<Uuid>, Path, description = "Todo database id to archive Todo for"),
+    )
+    )]
+    async fn todos_archive(
+        Path(id): Path<Uuid>,
+        State(state): State<AppState>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        // Lock order is active store, then archive, everywhere
+        let mut todos = state.db.write()?;
+        let mut archive = state.archive.write()?;
+        flush_pending_update(&state, &mut todos, &id);
+
+        let todo = todos.get(&id).ok_or(ApiError::NotFound)?;
+        if !todo.completed {
+            return Err(StoreError::Conflict.into());
+        }
+
+        let mut todo = remove_todo(&state, &mut todos, &id).unwrap();
+        todo.position = state.next_archive_position.fetch_add(1, Ordering::Relaxed);
+        archive.insert(id, todo.clone());
+
+        Ok(state.represent(&todo))
+    }
+
+    /// Unarchive todo by id
+    ///
+    /// Move an archived todo back to the end of the active list
+    #[utoipa::path(
+    post,
+    path = "/todos/{id}/unarchive",
+    responses(
+        (status = 200, description = "Todo unarchived successfully", body = Todo),
+        (status = NOT_FOUND, description = "Todo was not found in the archive"),
+        (status = INSUFFICIENT_STORAGE, description = "Todo store is full")
+    ),
+    params(
+        ("id" = Path<Uuid>, Path, description = "Todo database id to unarchive Todo for"),
+    )
+    )]
+    async fn todos_unarchive(
+        Path(id): Path<Uuid>,
+        State(state): State<AppState>,
+    ) -> Result<impl IntoResponse, ApiError> {
+        let mut todos = state.db.write()?;
+        let mut archive = state.archive.write()?;
+
+        let todo = archive.get(&id).ok_or(ApiError::NotFound)?.clone();
+        let todo = store_todo(&state, &mut todos, todo)?;
+        archive.remove(&id);
+
+        Ok(state.represent(&todo))
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+    #[serde(rename_all = "lowercase")]
+    pub(crate) enum StatsBucket {
+        #[default]
+        Day,
+        Week,
+    }
+
+    // The query parameters for todos stats
+    #[derive(Debug, Deserialize, Default, ToSchema)]
+    struct StatsQuery {
+        #[serde(default)]
+        bucket: StatsBucket,
+    }
+
+    #[derive(Debug, Serialize, ToSchema)]
+    struct TodoStats {
+        total: usize,
+        completed: usize,
+        completions: Vec<CompletionBucket>,
+    }
+
+    #[derive(Debug, Serialize, PartialEq, Eq, ToSchema)]
+    pub(crate) struct CompletionBucket {
+        // First day of the bucket
+        pub(crate) start: NaiveDate,
+        pub(crate) count: usize,
+    }
+
+    /// Get todo stats
+    ///
+    /// Get todo totals and a histogram of completions per day or week
+    #[utoipa::path(
+    get,
+    path = "/todos/stats",
+    responses(
+        (status = 200, description = "Todo stats computed successfully", body = TodoStats)
+    ),
+    params(
+        ("bucket" = Option<StatsBucket>, Query, description = "Completion histogram granularity, `day` (default) or `week`"),
+    )
+    )]
+    async fn todos_stats(
+        Query(query): Query<StatsQuery>,
+        State(counters): State<Counters>,
+    ) -> impl IntoResponse {
+        // Served from running counts, so stats don't scan the store under its lock
+        let counts = counters.snapshot();
+
+        let stats = TodoStats {
+            total: counts.total,
+            completed: counts.completed,
+            completions: completion_histogram(&counts.completions_by_day, query.bucket),
+        };
+        Json(stats)
+    }
+
+    // Sum daily completion counts per bucket, ordered by bucket start
+    pub(crate) fn completion_histogram(
+        completions_by_day: &BTreeMap<NaiveDate, usize>,
+        bucket: StatsBucket,
+    ) -> Vec<CompletionBucket> {
+        let mut counts = BTreeMap::<NaiveDate, usize>::new();
+
+        for (&day, &count) in completions_by_day {
+            let start = match bucket {
+                StatsBucket::Day => day,
+                StatsBucket::Week => {
+                    day - ChronoDuration::days(day.weekday().num_days_from_monday().into())
+                }
+            };
+            *counts.entry(start).or_default() += count;
+        }
+
+        counts
+            .into_iter()
+            .map(|(start, count)| CompletionBucket { start, count })
+            .collect()
+    }
+
+    #[derive(Debug, Serialize, ToSchema)]
+    pub(crate) struct VersionInfo {
+        version: &'static str,
+        rustc: &'static str,
+        commit: &'static str,
+    }
+
+    /// Get the service version
+    ///
+    /// Get the version, compiler and commit the service was built from, for client
+    /// compatibility checks
+    #[utoipa::path(
+    get,
+    path = "/version",
+    responses(
+        (status = 200, description = "Build metadata of the running service", body = VersionInfo)
+    )
+    )]
+    async fn version() -> impl IntoResponse {
+        Json(VersionInfo {
+            version: build_info::VERSION,
+            rustc: build_info::RUSTC_VERSION,
+            commit: build_info::GIT_HASH,
+        })
+    }
+
+    pub(crate) type Db = Arc<RwLock<HashMap<Uuid, Todo>>>;
+
+    #[derive(Clone)]
+    pub(crate) struct AppState {
+        pub(crate) db: Db,
+        pub(crate) access_log: AccessLog,
+        pub(crate) config: Arc<Config>,
+        pub(crate) counters: Counters,
+        // Archived todos, out of the active listing and stats
+        pub(crate) archive: Db,
+        // Position of the next todo stored or archived, after those of every todo so far.
+        // Only taken under the write lock of the store it is for
+        pub(crate) next_position: Arc<AtomicU64>,
+        pub(crate) next_archive_position: Arc<AtomicU64>,
+        pub(crate) ids: IdPool,
+        // Updates waiting out `update_debounce`
+        pub(crate) pending_updates: Arc<Mutex<HashMap<Uuid, PendingUpdate>>>,
+        // Stamps todos, request traces and sessions; a `MockClock` in tests
+        pub(crate) clock: SharedClock,
+    }
+
+    impl AppState {
+        pub(crate) fn new(config: Config) -> Self {
+            AppState {
+                db: Db::default(),
+                access_log: AccessLog::default(),
+                counters: Counters::default(),
+                archive: Db::default(),
+                next_position: Arc::default(),
+                next_archive_position: Arc::default(),
+                ids: IdPool::new(config.id_pool_size),
+                pending_updates: Arc::default(),
+                clock: Arc::new(SystemClock),
+                config: Arc::new(config),
+            }
+        }
+
+        pub(crate) fn now(&self) -> DateTime<Utc> {
+            self.clock.now().into()
+        }
+
+        pub(crate) fn representation(&self) -> Representation {
+            Representation::from(&*self.config)
+        }
+
+        // JSON response body of `value`, spelled as configured
+        pub(crate) fn represent<T: Represent>(&self, value: &T) -> Json<T::Body> {
+            Json(value.represent(self.representation()))
+        }
+
+        // Load previously stored todos, e.g. a snapshot read at startup
+        pub(crate) fn restore(&self, restored: impl IntoIterator<Item = Todo>) {
+            let mut todos = self.db.write().unwrap();
+            for todo in restored {
+                self.access_log.touch(todo.id);
+                self.counters.add(&todo);
+                self.next_position
+                    .fetch_max(todo.position + 1, Ordering::Relaxed);
+                if let Some(replaced) = todos.insert(todo.id, todo) {
+                    self.counters.remove(&replaced);
+                }
+            }
+        }
+
+        // Load previously archived todos, which stay out of the counts
+        pub(crate) fn restore_archived(&self, restored: impl IntoIterator<Item = Todo>) {
+            let mut archive = self.archive.write().unwrap();
+            for todo in restored {
+                self.next_archive_position
+                    .fetch_max(todo.position + 1, Ordering::Relaxed);
+                archive.insert(todo.id, todo);
+            }
+        }
+    }
+
+    impl FromRef<AppState> for Db {
+        fn from_ref(state: &AppState) -> Self {
+            state.db.clone()
+        }
+    }
+
+    impl FromRef<AppState> for AccessLog {
+        fn from_ref(state: &AppState) -> Self {
+            state.access_log.clone()
+        }
+    }
+
+    impl FromRef<AppState> for Arc<Config> {
+        fn from_ref(state: &AppState) -> Self {
+            state.config.clone()
+        }
+    }
+
+    impl FromRef<AppState> for Representation {
+        fn from_ref(state: &AppState) -> Self {
+            state.representation()
+        }
+    }
+
+    impl FromRef<AppState> for Counters {
+        fn from_ref(state: &AppState) -> Self {
+            state.counters.clone()
+        }
+    }
+
+    // Totals behind /todos/stats
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub(crate) struct TodoCounts {
+        pub(crate) total: usize,
+        pub(crate) completed: usize,
+        pub(crate) completions_by_day: BTreeMap<NaiveDate, usize>,
+    }
+
+    impl TodoCounts {
+        // Count from scratch; the running counts must always agree with this
+        #[cfg(test)]
+        pub(crate) fn scan<'a>(todos: impl Iterator<Item = &'a Todo>) -> Self {
+            let mut counts = TodoCounts::default();
+            for todo in todos {
+                counts.add(todo);
+            }
+            counts
+        }
+
+        fn add(&mut self, todo: &Todo) {
+            self.total += 1;
+            if todo.completed {
+                self.completed += 1;
+            }
+            if let Some(completed_at) = todo.completed_at {
+                *self
+                    .completions_by_day
+                    .entry(completed_at.date_naive())
+                    .or_default() += 1;
+            }
+        }
+
+        fn remove(&mut self, todo: &Todo) {
+            self.total -= 1;
+            if todo.completed {
+                self.completed -= 1;
+            }
+            if let Some(completed_at) = todo.completed_at {
+                let day = completed_at.date_naive();
+                if let Some(count) = self.completions_by_day.get_mut(&day) {
+                    *count -= 1;
+                    if *count == 0 {
+                        self.completions_by_day.remove(&day);
+                    }
+                }
+            }
+        }
+    }
+
+    // Running counts, updated by every change to the store while its write lock is held.
+    // A change is recorded as removing the old todo and adding the new one
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct Counters(Arc<Mutex<TodoCounts>>);
+
+    impl Counters {
+        pub(crate) fn snapshot(&self) -> TodoCounts {
+            self.0.lock().unwrap().clone()
+        }
+
+        fn add(&self, todo: &Todo) {
+            self.0.lock().unwrap().add(todo);
+        }
+
+        fn remove(&self, todo: &Todo) {
+            self.0.lock().unwrap().remove(todo);
+        }
+    }
+
+    // Tracks when each todo was last read or written on its own, used for LRU eviction.
+    // Listings don't count, or paging through every todo would make them all recent
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct AccessLog(Arc<Mutex<AccessLogEntries>>);
+
+    #[derive(Debug, Default)]
+    struct AccessLogEntries {
+        // Logical clock, bumped on every access
+        tick: u64,
+        last_access: HashMap<Uuid, u64>,
+        // The same accesses ordered by tick, so the least recent is the first entry
+        by_tick: BTreeMap<u64, Uuid>,
+    }
+
+    impl AccessLog {
+        pub(crate) fn touch(&self, id: Uuid) {
+            let mut entries = self.0.lock().unwrap();
+            entries.tick += 1;
+            let tick = entries.tick;
+            if let Some(previous) = entries.last_access.insert(id, tick) {
+                entries.by_tick.remove(&previous);
+            }
+            entries.by_tick.insert(tick, id);
+        }
+
+        pub(crate) fn forget(&self, id: &Uuid) {
+            let mut entries = self.0.lock().unwrap();
+            if let Some(tick) = entries.last_access.remove(id) {
+                entries.by_tick.remove(&tick);
+            }
+        }
+
+        fn least_recent(&self) -> Option<Uuid> {
+            self.0
+                .lock()
+                .unwrap()
+                .by_tick
+                .first_key_value()
+                .map(|(_, id)| *id)
+        }
+    }
+
+    // Todo ids pregenerated by a background thread, so a burst of creates doesn't pay
+    // for `Uuid::new_v4()` (~390ns, vs ~45ns to take one from a warm pool)
+    #[derive(Clone, Default)]
+    pub(crate) struct IdPool(Option<Arc<Mutex<mpsc::Receiver<Uuid>>>>);
+
+    impl IdPool {
+        fn new(capacity: usize) -> Self {
+            if capacity == 0 {
+                return IdPool(None);
+            }
+
+            let (sender, receiver) = mpsc::sync_channel(capacity);
+            // Blocks while the pool is full and exits once the pool is dropped
+            std::thread::spawn(move || while sender.send(Uuid::new_v4()).is_ok() {});
+            IdPool(Some(Arc::new(Mutex::new(receiver))))
+        }
+
+        // Take a pregenerated id, generating one inline when the pool is drained
+        pub(crate) fn next(&self) -> Uuid {
+            self.0
+                .as_ref()
+                .and_then(|receiver| receiver.lock().unwrap().try_recv().ok())
+                .unwrap_or_else(Uuid::new_v4)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+    pub(crate) struct Todo {
+        pub(crate) id: Uuid,
+        pub(crate) text: String,
+        pub(crate) completed: bool,
+        #[serde(default, deserialize_with = "deserialize_optional_timestamp")]
+        pub(crate) completed_at: Option<DateTime<Utc>>,
+        // Snapshots written before these existed load them as the Unix epoch
+        #[serde(default, deserialize_with = "deserialize_timestamp")]
+        pub(crate) created_at: DateTime<Utc>,
+        // Bumped by every update to text or completion
+        #[serde(default, deserialize_with = "deserialize_timestamp")]
+        pub(crate) updated_at: DateTime<Utc>,
+        // Sort key for `sort_by=position`, see `POST /todos/reorder`
+        #[serde(default)]
+        pub(crate) position: u64,
+    }
+
+    impl std::fmt::Debug for Todo {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Todo")
+                .field("id", &self.id)
+                .field("text", &LoggedText(&self.text))
+                .field("completed", &self.completed)
+                .field("completed_at", &self.completed_at)
+                .field("created_at", &self.created_at)
+                .field("updated_at", &self.updated_at)
+                .field("position", &self.position)
+                .finish()
+        }
+    }
+
+    // Todo text may be sensitive, so Debug output, and with it every log line and span
+    // field, shows it redacted unless built with the `log-todo-text` feature
+    struct LoggedText<'a>(&'a str);
+
+    impl std::fmt::Debug for LoggedText<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            if cfg!(feature = "log-todo-text") {
+                std::fmt::Debug::fmt(self.0, f)
+            } else {
+                f.write_str(crate::config::REDACTED)
+            }
+        }
+    }
+}
+
+// Why `run` gave up, for the embedder to report and pick an exit code for
+#[derive(Debug, thiserror::Error)]
+pub enum RunError {
+    #[error("aborting startup: {0}")]
+    NotReady(#[from] rest_actuator::api::NotReadyError),
+    #[error("failed to bind {addr}: {source}")]
+    Bind {
+        addr: std::net::SocketAddr,
+        source: std::io::Error,
+    },
+    #[error("server failed: {0}")]
+    Serve(std::io::Error),
+}
+
+// Log the effective configuration, bind and serve the app until shutdown
+pub async fn run(config: config::Config) -> Result<(), RunError> {
+    config.log_effective();
+
+    let state = api::AppState::new(config.clone());
+
+    if let Some(persist_path) = &config.persist_path {
+        match persist::load(persist_path) {
+            Ok(snapshot) => {
+                tracing::info!(
+                    "restored {} todos and {} archived todos from {}",
+                    snapshot.todos.len(),
+                    snapshot.archived.len(),
+                    persist_path.display()
+                );
+                state.restore(snapshot.todos);
+                state.restore_archived(snapshot.archived);
+            }
+            Err(err) => tracing::error!("failed to restore todos: {err}"),
+        }
+        persist::spawn_flusher(state.clone(), persist_path.clone(), config.persist_interval);
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_bind_addr) = config.grpc_bind_addr {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = grpc::serve(grpc_bind_addr, state).await {
+                tracing::error!("gRPC server failed: {err}");
+            }
+        });
+    }
+    #[cfg(not(feature = "grpc"))]
+    if config.grpc_bind_addr.is_some() {
+        tracing::warn!("GRPC_BIND_ADDR is set but the `grpc` feature is not enabled");
+    }
+
+    let actuator_state = api::actuator_state(&config);
+    if let Some(timeout) = config.startup_ready_timeout {
+        actuator_state.wait_for_ready(timeout).await?;
+    }
+
+    let app = api::app_with_actuator(state.clone(), actuator_state);
+
+    let listener = tokio::net::TcpListener::bind(config.bind_addr)
+        .await
+        .map_err(|source| RunError::Bind {
+            addr: config.bind_addr,
+            source,
+        })?;
+    if let Ok(addr) = listener.local_addr() {
+        tracing::debug!("listening on {addr}");
+    }
+    let served = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .map_err(RunError::Serve);
+
+    // Changes since the last periodic flush would be lost otherwise
+    if let Some(persist_path) = config.persist_path {
+        persist::flush_and_log(state, persist_path).await;
+    }
+    served
+}
+
+// Ctrl+C, or SIGTERM as sent by container runtimes
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to listen for Ctrl+C: {err}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("failed to listen for SIGTERM: {err}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {}
+        () = terminate => {}
+    }
+    tracing::info!("shutting down");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::{
+        body::Body,
+        extract::connect_info::MockConnectInfo,
+        http::{self, Request, StatusCode},
+    };
+    use config::{Config, CorsConfig, EvictionPolicy, FieldNaming, Middleware, TextOverflow};
+    use http_body_util::BodyExt; // for `collect`
+    use serde_json::{json, Value};
+    use std::net::SocketAddr;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tower::{Service, ServiceExt}; // for `call`, `oneshot`, and `ready`
+
+    #[tokio::test]
+    async fn todos_get() {
+        let app = api::app();
+
+        // `Router` implements `tower::Service<Request<Body>>` so we can
+        // call it like any tower service, no need to run an HTTP server.
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri("/todos")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"[]");
+    }
+
+    #[tokio::test]
+    async fn todos_get_plus_query() {
+        let app = api::app();
+
+        // `Router` implements `tower::Service<Request<Body>>` so we can
+        // call it like any tower service, no need to run an HTTP server.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri("/todos?offset=0&limit=0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"[]");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::GET)
+                    .uri("/todos?offset=0&limit=2")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"[]");
+    }
+
+    #[tokio::test]
+    async fn json() {
+        let app = api::app();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/json")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(&json!([1, 2, 3, 4])).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, json!({ "data": [1, 2, 3, 4] }));
+    }
+
+    async fn create_todo(app: &Router, text: &str) -> Value {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/todos")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(&json!({ "text": text })).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    async fn merge_patch_todo(app: &Router, id: &str, patch: Value) -> (StatusCode, Value) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::PATCH)
+                    .uri(format!("/todos/{id}"))
+                    .header(http::header::CONTENT_TYPE, api::MERGE_PATCH_CONTENT_TYPE)
+                    .body(Body::from(serde_json::to_vec(&patch).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn merge_patch_sets_field() {
+        let app = api::app();
+        let todo = create_todo(&app, "buy milk").await;
+        let id = todo["id"].as_str().unwrap();
+
+        let (status, body) = merge_patch_todo(&app, id, json!({ "text": "buy oat milk" })).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["text"], "buy oat milk");
+        assert_eq!(body["completed"], false);
+    }
+
+    #[tokio::test]
+    async fn merge_patch_leaves_absent_fields() {
+        let app = api::app();
+        let todo = create_todo(&app, "buy milk").await;
+        let id = todo["id"].as_str().unwrap();
+
+        let (status, body) = merge_patch_todo(&app, id, json!({ "completed": true })).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["text"], "buy milk");
+        assert_eq!(body["completed"], true);
+    }
+
+    #[tokio::test]
+    async fn merge_patch_null_clears_field() {
+        let app = api::app();
+        let todo = create_todo(&app, "buy milk").await;
+        let id = todo["id"].as_str().unwrap();
+
+        // `text` is required, so clearing it with null is rejected and nothing changes
+        let (status, _) = merge_patch_todo(&app, id, json!({ "text": null })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, body) = merge_patch_todo(&app, id, json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["text"], "buy milk");
+    }
+
+    #[tokio::test]
+    async fn cors_preflight_reflects_policy() {
+        let app = api::app_with_config(Config {
+            cors: Some(CorsConfig {
+                allowed_origins: vec!["https://app.example.com".to_string()],
+                allow_credentials: true,
+                allowed_methods: vec![http::Method::GET, http::Method::POST],
+                allowed_headers: vec!["content-type".to_string(), "x-request-id".to_string()],
+                exposed_headers: vec!["x-app-version".to_string()],
+            }),
+            ..Config::default()
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::OPTIONS)
+                    .uri("/todos")
+                    .header(http::header::ORIGIN, "https://app.example.com")
+                    .header(http::header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers[http::header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(
+            headers[http::header::ACCESS_CONTROL_ALLOW_METHODS],
+            "GET,POST"
+        );
+        assert_eq!(
+            headers[http::header::ACCESS_CONTROL_ALLOW_HEADERS],
+            "content-type,x-request-id"
+        );
+        assert_eq!(
+            headers[http::header::ACCESS_CONTROL_ALLOW_CREDENTIALS],
+            "true"
+        );
+    }
+
+    #[tokio::test]
+    async fn app_with_cors_allows_configured_origins() {
+        let app = api::app_with_cors(vec!["https://app.example.com".to_string()]);
+        let request = |method: http::Method, origin: &str| {
+            Request::builder()
+                .method(method)
+                .uri("/todos")
+                .header(http::header::ORIGIN, origin)
+                .header(http::header::ACCESS_CONTROL_REQUEST_METHOD, "DELETE")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app
+            .clone()
+            .oneshot(request(http::Method::OPTIONS, "https://app.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[http::header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(
+            response.headers()[http::header::ACCESS_CONTROL_ALLOW_METHODS],
+            "GET,POST,PUT,PATCH,DELETE"
+        );
+
+        let response = app
+            .clone()
+            .oneshot(request(http::Method::GET, "https://app.example.com"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[http::header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+
+        let response = app
+            .oneshot(request(http::Method::GET, "https://evil.example.com"))
+            .await
+            .unwrap();
+        assert!(!response
+            .headers()
+            .contains_key(http::header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn strict_query_rejects_unknown_params() {
+        let app = api::app_with_config(Config {
+            strict_query: true,
+            ..Config::default()
+        });
+        let todo = create_todo(&app, "typo").await;
+        let get = |uri: String| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                (
+                    status,
+                    serde_json::from_slice::<Value>(&body).unwrap_or(Value::Null),
+                )
+            }
+        };
+
+        let (status, body) = get("/todos?limmit=5".to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "bad_request");
+        let message = body["error"]["message"].as_str().unwrap();
+        assert!(message.contains("`limmit`"), "{message}");
+        assert!(message.contains("limit"), "{message}");
+
+        // Every known parameter still goes through
+        let (status, _) = get(format!(
+            "/todos?offset=0&limit=5&completed=false&q=typo&order=asc&view=full&id={}",
+            todo["id"].as_str().unwrap()
+        ))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = get("/todos/stats?bucket=day".to_string()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Known on one route is not known on all
+        let (status, _) = get(format!("/todos/{}?limit=5", todo["id"].as_str().unwrap())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Off by default
+        let response = api::app()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos?limmit=5")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn cors_disabled_by_default() {
+        let app = api::app();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/todos")
+                    .header(http::header::ORIGIN, "https://app.example.com")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert!(!response
+            .headers()
+            .contains_key(http::header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    async fn list_todos(app: &Router) -> Vec<Value> {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    // `list_todos`, negotiated as MessagePack. Ids are bytes there, so decode into `T`
+    // rather than a JSON value
+    async fn list_todos_msgpack<T: serde::de::DeserializeOwned>(app: &Router) -> Vec<T> {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos")
+                    .header(http::header::ACCEPT, api::MSGPACK_CONTENT_TYPE)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        rmp_serde::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn internal_errors_are_logged_not_sent() {
+        let logs = config::tests::CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let error = || tower::BoxError::from("connect to db://admin:hunter2@db failed");
+        let response = api::handle_middleware_error(error(), false).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "internal");
+        assert_eq!(body["error"]["message"], "internal error");
+        assert!(!body.to_string().contains("hunter2"));
+        let correlation_id = body["error"]["correlation_id"].as_str().unwrap();
+        assert!(correlation_id.parse::<uuid::Uuid>().is_ok());
+
+        // The log ties the detail to the id the client saw
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let line = output
+            .lines()
+            .find(|line| line.contains(correlation_id))
+            .unwrap();
+        assert!(line.contains("connect to db://admin:hunter2@db failed"));
+
+        // Opting in sends the detail along
+        let response = api::handle_middleware_error(error(), true).await;
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("hunter2"));
+        assert!(body["error"]["correlation_id"].is_string());
+    }
+
+    #[cfg(not(feature = "log-todo-text"))]
+    #[test]
+    fn todo_text_is_redacted_from_logs() {
+        let logs = config::tests::CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+
+        let state = api::AppState::new(Config::default());
+        let todo = tracing::subscriber::with_default(subscriber, || {
+            let mut todo = api::insert_todo(&state, "call the bank".to_string(), false).unwrap();
+            let update = api::UpdateTodo {
+                text: Some("pin is 1234".to_string()),
+                completed: None,
+            };
+            tracing::debug!(?update, "applying update");
+            api::apply_update(&state, &mut todo, update).unwrap();
+            todo
+        });
+
+        let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("stored todo"));
+        assert!(output.contains("updated todo"));
+        assert!(output.contains(&todo.id.to_string()));
+        assert!(output.contains("text: [REDACTED]"));
+        assert!(!output.contains("call the bank"));
+        assert!(!output.contains("pin is 1234"));
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_completed() {
+        let app = api::app();
+        let open = create_todo(&app, "open").await;
+        let done = create_todo(&app, "done").await;
+        let id = done["id"].as_str().unwrap();
+        let (status, done) = merge_patch_todo(&app, id, json!({ "completed": true })).await;
+        assert_eq!(status, StatusCode::OK);
+
+        for (query, expected) in [("?completed=true", &done), ("?completed=false", &open)] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .uri(format!("/todos{query}"))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let body: Vec<Value> = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body, vec![expected.clone()]);
+        }
+
+        assert_eq!(list_todos(&app).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn list_sorts_by_field_and_order() {
+        let app = api::app();
+        let banana = create_todo(&app, "banana").await;
+        let apple = create_todo(&app, "apple").await;
+        let cherry = create_todo(&app, "cherry").await;
+        let (_, apple) = merge_patch_todo(
+            &app,
+            apple["id"].as_str().unwrap(),
+            json!({ "completed": true }),
+        )
+        .await;
+
+        let list = |query: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        Request::builder()
+                            .uri(format!("/todos{query}"))
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                (status, serde_json::from_slice::<Value>(&body).unwrap())
+            }
+        };
+
+        // Creation order by default, and the same on every request
+        for _ in 0..3 {
+            let (status, body) = list("").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body, json!([banana, apple, cherry]));
+        }
+
+        for (query, expected) in [
+            ("?sort_by=text", json!([apple, banana, cherry])),
+            ("?sort_by=text&order=desc", json!([cherry, banana, apple])),
+            (
+                "?sort_by=created_at&order=desc",
+                json!([cherry, apple, banana]),
+            ),
+            // Open todos first, ties in creation order
+            ("?sort_by=completed", json!([banana, cherry, apple])),
+            ("?sort_by=text&limit=2", json!([apple, banana])),
+        ] {
+            let (status, body) = list(query).await;
+            assert_eq!(status, StatusCode::OK, "{query}");
+            assert_eq!(body, expected, "{query}");
+        }
+
+        let (status, body) = list("?sort_by=priority").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], "bad_request");
+    }
+
+    #[tokio::test]
+    async fn deprecated_fields_get_a_warning_header() {
+        let app = api::app_with_config(Config {
+            deprecated_fields: vec!["completed".to_string()],
+            ..Config::default()
+        });
+
+        let create = |body: &'static str| {
+            let app = app.clone();
+            async move {
+                app.oneshot(
+                    Request::builder()
+                        .method(http::Method::POST)
+                        .uri("/todos")
+                        .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        // The field still works
+        let response = create(r#"{"text":"old client","completed":true}"#).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.headers()[http::header::WARNING],
+            r#"299 - "field `completed` is deprecated""#
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let todo: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(todo["completed"], true);
+
+        let response = create(r#"{"text":"new client"}"#).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().get(http::header::WARNING).is_none());
+    }
+
+    #[tokio::test]
+    async fn bulk_create_is_all_or_nothing() {
+        let app = api::app_with_config(Config {
+            max_batch_size: Some(3),
+            ..Config::default()
+        });
+
+        let bulk_create = |body: Value| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        Request::builder()
+                            .method(http::Method::POST)
+                            .uri("/todos/bulk")
+                            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                            .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                (status, serde_json::from_slice::<Value>(&body).unwrap())
+            }
+        };
+
+        let (status, body) = bulk_create(json!([
+            { "text": "first" },
+            { "text": "second", "completed": true },
+        ]))
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body[0]["text"], "first");
+        assert_eq!(body[1]["text"], "second");
+        assert_eq!(body[1]["completed"], true);
+        let created = body.as_array().unwrap().clone();
+
+        // The failing index is reported and nothing is stored
+        let (status, body) = bulk_create(json!([{ "text": "third" }, { "text": " " }])).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["message"], "todo 1: text must not be empty");
+
+        let (status, _) = bulk_create(
+            json!([{ "text": "a" }, { "text": "b" }, { "text": "c" }, { "text": "d" }]),
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        assert_eq!(list_todos(&app).await, created);
+    }
+
+    #[tokio::test]
+    async fn config_service_check_follows_availability() {
+        use rest_actuator::api::StateChecker;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let available = Arc::new(AtomicBool::new(true));
+        let config_service = Router::new()
+            .route(
+                "/flags",
+                axum::routing::get(
+                    |axum::extract::State(available): axum::extract::State<Arc<AtomicBool>>| async move {
+                        if available.load(Ordering::Relaxed) {
+                            StatusCode::OK
+                        } else {
+                            StatusCode::SERVICE_UNAVAILABLE
+                        }
+                    },
+                ),
+            )
+            .with_state(available.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, config_service).await.unwrap() });
+
+        let check = api::ConfigServiceHealthCheck::spawn(
+            format!("http://{addr}/flags"),
+            std::time::Duration::from_millis(10),
+        );
+        let check = &check;
+        let readiness_becomes = |ready: bool| {
+            tokio::time::timeout(std::time::Duration::from_secs(5), async move {
+                while check.is_ready().await != ready {
+                    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                }
+            })
+        };
+
+        readiness_becomes(true).await.unwrap();
+        assert!(check.is_alive().await);
+
+        available.store(false, Ordering::Relaxed);
+        readiness_becomes(false).await.unwrap();
+        assert!(check.is_alive().await);
+
+        available.store(true, Ordering::Relaxed);
+        readiness_becomes(true).await.unwrap();
+    }
+
+    #[test]
+    fn etag_is_stable() {
+        let timestamp = "2024-01-01T00:00:00Z".parse().unwrap();
+        let todo = api::Todo {
+            id: uuid::Uuid::nil(),
+            text: "buy milk".to_string(),
+            completed: false,
+            completed_at: None,
+            created_at: timestamp,
+            updated_at: timestamp,
+            position: 0,
+        };
+        // Pinned, so a change of hash or serialization that would break clients' cached
+        // tags across a deploy shows up here
+        assert_eq!(api::etag(&todo), "\"2e61c953420bf301e2f3c8941f828154\"");
+    }
+
+    #[tokio::test]
+    async fn patch_honors_if_match() {
+        let app = api::app();
+        let todo = create_todo(&app, "draft").await;
+        let uri = format!("/todos/{}", todo["id"].as_str().unwrap());
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let etag = response.headers()[http::header::ETAG].clone();
+
+        let patch = |content_type: &'static str, if_match: http::HeaderValue, body: Value| {
+            let app = app.clone();
+            let uri = uri.clone();
+            async move {
+                app.oneshot(
+                    Request::builder()
+                        .method(http::Method::PATCH)
+                        .uri(uri)
+                        .header(http::header::CONTENT_TYPE, content_type)
+                        .header(http::header::IF_MATCH, if_match)
+                        .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        let response = patch(
+            "application/merge-patch+json",
+            etag.clone(),
+            json!({ "text": "first edit" }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let current = response.headers()[http::header::ETAG].clone();
+        assert_ne!(current, etag);
+
+        // Either body format is refused with the stale ETag
+        for content_type in ["application/json", "application/merge-patch+json"] {
+            let response = patch(content_type, etag.clone(), json!({ "text": "clobber" })).await;
+            assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        }
+        assert_eq!(list_todos(&app).await[0]["text"], "first edit");
+
+        let response = patch("application/json", current, json!({ "completed": true })).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(list_todos(&app).await[0]["completed"], true);
+    }
+
+    #[tokio::test]
+    async fn update_honors_if_match() {
+        let app = api::app();
+        let todo = create_todo(&app, "draft").await;
+        let uri = format!("/todos/{}", todo["id"].as_str().unwrap());
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[http::header::ETAG].clone();
+        assert!(etag.to_str().unwrap().starts_with('"'));
+
+        let put = |if_match: http::HeaderValue, text: &'static str| {
+            let app = app.clone();
+            let uri = uri.clone();
+            async move {
+                app.oneshot(
+                    Request::builder()
+                        .method(http::Method::PUT)
+                        .uri(uri)
+                        .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                        .header(http::header::IF_MATCH, if_match)
+                        .body(Body::from(
+                            serde_json::to_vec(&json!({ "text": text })).unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        // Strong comparison: the weak form of the same tag doesn't match
+        let weak = format!("W/{}", etag.to_str().unwrap()).parse().unwrap();
+        let response = put(weak, "first edit").await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let response = put(etag.clone(), "first edit").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(response.headers()[http::header::ETAG], etag);
+
+        // A second client still holding the old ETag doesn't clobber the first edit
+        let response = put(etag, "second edit").await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "precondition_failed");
+        assert_eq!(list_todos(&app).await[0]["text"], "first edit");
+    }
+
+    #[tokio::test]
+    async fn delete_honors_if_match() {
+        let app = api::app();
+        let todo = create_todo(&app, "draft").await;
+        let uri = format!("/todos/{}", todo["id"].as_str().unwrap());
+
+        let etag = |app: Router| {
+            let uri = uri.clone();
+            async move {
+                let response = app
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                response.headers()[http::header::ETAG].clone()
+            }
+        };
+        let delete = |if_match: http::HeaderValue| {
+            let app = app.clone();
+            let uri = uri.clone();
+            async move {
+                app.oneshot(
+                    Request::builder()
+                        .method(http::Method::DELETE)
+                        .uri(uri)
+                        .header(http::header::IF_MATCH, if_match)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap()
+                .status()
+            }
+        };
+
+        let stale = etag(app.clone()).await;
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::PUT)
+                    .uri(&uri)
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(r#"{"completed":true}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Changed since the client last saw it
+        assert_eq!(delete(stale).await, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(list_todos(&app).await.len(), 1);
+
+        let current = etag(app.clone()).await;
+        assert_eq!(delete(current).await, StatusCode::NO_CONTENT);
+        assert!(list_todos(&app).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn update_can_return_diff() {
+        let app = api::app();
+        let todo = create_todo(&app, "write tests").await;
+        let id = todo["id"].as_str().unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::PUT)
+                    .uri(format!("/todos/{id}?return=diff"))
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(&json!({ "text": "write tests", "completed": true }))
+                            .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key(http::header::ETAG));
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "id": id,
+                "changes": { "completed": { "old": false, "new": true } },
+            })
+        );
+        assert_eq!(list_todos(&app).await[0]["completed"], true);
+    }
+
+    #[tokio::test]
+    async fn debounced_updates_apply_only_the_last() {
+        let state = api::AppState::new(Config {
+            update_debounce: Some(std::time::Duration::from_millis(100)),
+            ..Config::default()
+        });
+        let app = api::app_with_state(state.clone());
+        let todo = create_todo(&app, "draft").await;
+        let uri = format!("/todos/{}", todo["id"].as_str().unwrap());
+        let get_etag = || async {
+            let response = app
+                .clone()
+                .oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            response.headers()[http::header::ETAG].clone()
+        };
+
+        // Each update is conditional on the todo promised by the previous `202`
+        let mut etag = get_etag().await;
+        for (text, completed) in [("first", true), ("second", false), ("final", true)] {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method(http::Method::PUT)
+                        .uri(&uri)
+                        .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                        .header(http::header::IF_MATCH, etag)
+                        .body(Body::from(
+                            serde_json::to_vec(&json!({ "text": text, "completed": completed }))
+                                .unwrap(),
+                        ))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+            etag = response.headers()[http::header::ETAG].clone();
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["text"], text);
+        }
+        // Nothing is stored before the window is over
+        assert_eq!(list_todos(&app).await[0]["text"], "draft");
+
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        let todos = list_todos(&app).await;
+        assert_eq!(todos[0]["text"], "final");
+        assert_eq!(todos[0]["completed"], true);
+        assert_eq!(state.counters.snapshot().completed, 1);
+        assert!(state.pending_updates.lock().unwrap().is_empty());
+        // The stored todo is the one the last `202` promised
+        assert_eq!(get_etag().await, etag);
+    }
+
+    #[tokio::test]
+    async fn patch_during_debounce_window_is_kept() {
+        let state = api::AppState::new(Config {
+            update_debounce: Some(std::time::Duration::from_millis(100)),
+            ..Config::default()
+        });
+        let app = api::app_with_state(state.clone());
+        let todo = create_todo(&app, "draft").await;
+        let id = todo["id"].as_str().unwrap();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::PUT)
+                    .uri(format!("/todos/{id}"))
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(r#"{"text":"queued"}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        // Lands on top of the queued update rather than under it
+        let (status, patched) = merge_patch_todo(&app, id, json!({ "completed": true })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(patched["text"], "queued");
+        assert_eq!(patched["completed"], true);
+
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        let todos = list_todos(&app).await;
+        assert_eq!(todos[0]["text"], "queued");
+        assert_eq!(todos[0]["completed"], true);
+        assert_eq!(state.counters.snapshot().completed, 1);
+    }
+
+    #[test]
+    fn pagination_resolves_defaults() {
+        use api::{Pagination, PaginationResolved, SortBy, SortOrder, TodoView};
+
+        let defaults = PaginationResolved {
+            offset: 0,
+            limit: usize::MAX,
+            id: Vec::new(),
+            completed: None,
+            q: None,
+            sort_by: SortBy::CreatedAt,
+            order: SortOrder::Asc,
+            after: None,
+            view: TodoView::Full,
+            count_only: false,
+        };
+        assert_eq!(Pagination::default().resolve().unwrap(), defaults);
+
+        let partial = Pagination {
+            limit: Some(10),
+            q: Some("Groceries".to_string()),
+            ..Pagination::default()
+        };
+        assert_eq!(
+            partial.resolve().unwrap(),
+            PaginationResolved {
+                limit: 10,
+                q: Some("groceries".to_string()),
+                ..defaults.clone()
+            }
+        );
+
+        let id = uuid::Uuid::new_v4();
+        let full = Pagination {
+            offset: Some(5),
+            limit: Some(10),
+            id: vec![id],
+            completed: Some(true),
+            q: Some("x".to_string()),
+            sort_by: Some(SortBy::Text),
+            order: SortOrder::Desc,
+            after: None,
+            view: TodoView::Summary,
+            count_only: false,
+        };
+        assert_eq!(
+            full.resolve().unwrap(),
+            PaginationResolved {
+                offset: 5,
+                limit: 10,
+                id: vec![id],
+                completed: Some(true),
+                q: Some("x".to_string()),
+                sort_by: SortBy::Text,
+                order: SortOrder::Desc,
+                after: None,
+                view: TodoView::Summary,
+                count_only: false,
+            }
+        );
+
+        // A cursor overrides the sort, and can't be combined with an offset
+        let cursor = Pagination {
+            after: Some(id),
+            sort_by: Some(SortBy::Text),
+            order: SortOrder::Desc,
+            ..Pagination::default()
+        };
+        assert_eq!(
+            cursor.resolve().unwrap(),
+            PaginationResolved {
+                after: Some(id),
+                sort_by: SortBy::Position,
+                ..defaults
+            }
+        );
+        let conflicting = Pagination {
+            offset: Some(1),
+            after: Some(id),
+            ..Pagination::default()
+        };
+        assert!(conflicting.resolve().is_err());
+    }
+
+    #[tokio::test]
+    async fn timestamps_can_be_epoch_millis() {
+        let state = api::AppState::new(Config::default());
+        let rfc3339_app = api::app_with_state(state.clone());
+        let millis_app = api::app_with_state(api::AppState {
+            config: std::sync::Arc::new(Config {
+                timestamp_format: config::TimestampFormat::EpochMillis,
+                ..Config::default()
+            }),
+            ..state
+        });
+
+        // The same stored todo, read through both formats
+        let todo = create_todo(&rfc3339_app, "stamped").await;
+        let id = todo["id"].as_str().unwrap();
+        let created_at = todo["created_at"]
+            .as_str()
+            .unwrap()
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .unwrap();
+
+        let millis_todo = list_todos(&millis_app).await.remove(0);
+        assert_eq!(millis_todo["created_at"], created_at.timestamp_millis());
+        assert_eq!(millis_todo["updated_at"], created_at.timestamp_millis());
+        assert_eq!(millis_todo["completed_at"], Value::Null);
+        assert_eq!(millis_todo["text"], "stamped");
+
+        #[derive(serde::Deserialize)]
+        struct MillisTodo {
+            created_at: i64,
+            updated_at: i64,
+            completed_at: Option<i64>,
+        }
+        let listed = list_todos_msgpack::<MillisTodo>(&millis_app).await;
+        assert_eq!(listed[0].created_at, created_at.timestamp_millis());
+        assert_eq!(listed[0].updated_at, created_at.timestamp_millis());
+        assert_eq!(listed[0].completed_at, None);
+
+        // Either format is accepted back in a merge patch
+        for (app, todo) in [(&millis_app, &millis_todo), (&rfc3339_app, &todo)] {
+            let mut patch = todo.clone();
+            patch["text"] = json!("restamped");
+            let (status, patched) = merge_patch_todo(app, id, patch).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(patched["text"], "restamped");
+            assert_eq!(patched["created_at"], todo["created_at"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn timestamps_follow_the_injected_clock() {
+        let clock = rest_actuator::api::MockClock::new(
+            std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000),
+        );
+        let app = api::app_with_state(api::AppState {
+            clock: std::sync::Arc::new(clock.clone()),
+            ..api::AppState::new(Config::default())
+        });
+
+        let todo = create_todo(&app, "stamped").await;
+        let id = todo["id"].as_str().unwrap();
+        assert_eq!(todo["created_at"], "2023-11-14T22:13:20Z");
+
+        clock.advance(std::time::Duration::from_secs(60));
+        let mut patch = todo.clone();
+        patch["completed"] = json!(true);
+        let (status, patched) = merge_patch_todo(&app, id, patch).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(patched["created_at"], "2023-11-14T22:13:20Z");
+        assert_eq!(patched["updated_at"], "2023-11-14T22:14:20Z");
+        assert_eq!(patched["completed_at"], "2023-11-14T22:14:20Z");
+    }
+
+    #[tokio::test]
+    async fn maintenance_mode_keeps_actuator_routes() {
+        let app = api::app();
+        let get = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap()
+                    .status()
+            }
+        };
+        let set_maintenance = |enabled: bool| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        Request::builder()
+                            .method(http::Method::POST)
+                            .uri("/actuator/maintenance")
+                            .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                            .body(Body::from(
+                                serde_json::to_vec(&json!({ "enabled": enabled })).unwrap(),
+                            ))
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+            }
+        };
+
+        set_maintenance(true).await;
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, json!({ "status": "MAINTENANCE" }));
+        assert_eq!(get("/actuator/health").await, StatusCode::OK);
+        assert_eq!(get("/actuator/health/liveness").await, StatusCode::OK);
+
+        set_maintenance(false).await;
+        assert_eq!(get("/todos").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn empty_responses_set_content_length() {
+        let app = api::app();
+        let todo = create_todo(&app, "short lived").await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::DELETE)
+                    .uri(format!("/todos/{}", todo["id"].as_str().unwrap()))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[http::header::CONTENT_LENGTH], "0");
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/no-such-route")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[http::header::CONTENT_LENGTH], "0");
+    }
+
+    #[tokio::test]
+    async fn list_searches_text() {
+        let app = api::app();
+        let groceries = create_todo(&app, "Buy Groceries").await;
+        let more_groceries = create_todo(&app, "groceries for the party").await;
+        create_todo(&app, "walk the dog").await;
+
+        let search = |query: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        Request::builder()
+                            .uri(format!("/todos?{query}"))
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                serde_json::from_slice::<Vec<Value>>(&body).unwrap()
+            }
+        };
+
+        let mut found = search("q=GROCERIES").await;
+        found.sort_by_key(|todo| todo["text"].as_str().unwrap().to_string());
+        assert_eq!(found, vec![groceries, more_groceries]);
+
+        assert_eq!(search("q=groceries&limit=1").await.len(), 1);
+        assert!(search("q=laundry").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn list_reports_total_count() {
+        let app = api::app();
+        for text in ["one", "two", "three"] {
+            create_todo(&app, text).await;
+        }
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/todos?limit=1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[api::TOTAL_COUNT_HEADER], "3");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn list_can_return_count_only() {
+        let app = api::app();
+        for text in ["one", "two", "three"] {
+            create_todo(&app, text).await;
+        }
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/todos?count_only=true&q=o&limit=1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[api::TOTAL_COUNT_HEADER], "2");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn configured_response_headers_are_set() {
+        let app = api::app_with_config(Config {
+            response_headers: vec![
+                (
+                    http::header::X_FRAME_OPTIONS,
+                    http::HeaderValue::from_static("DENY"),
+                ),
+                (
+                    http::header::CONTENT_SECURITY_POLICY,
+                    http::HeaderValue::from_static("default-src 'none'"),
+                ),
+            ],
+            ..Config::default()
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/todos")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[http::header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(
+            response.headers()[http::header::CONTENT_SECURITY_POLICY],
+            "default-src 'none'"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_can_mark_todo_completed() {
+        let app = api::app();
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/todos")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(r#"{"text":"imported","completed":true}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let todo: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(todo["completed"], true);
+        assert!(todo["completed_at"].is_string());
+
+        assert_eq!(list_todos(&app).await, vec![todo]);
+    }
+
+    #[tokio::test]
+    async fn patch_bumps_updated_at() {
+        let app = api::app();
+        let todo = create_todo(&app, "water plants").await;
+        assert_eq!(todo["created_at"], todo["updated_at"]);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::PATCH)
+                    .uri(format!("/todos/{}", todo["id"].as_str().unwrap()))
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(r#"{"completed":true}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let patched: Value = serde_json::from_slice(&body).unwrap();
+
+        let timestamp = |value: &Value| {
+            value
+                .as_str()
+                .unwrap()
+                .parse::<chrono::DateTime<chrono::Utc>>()
+                .unwrap()
+        };
+        assert_eq!(patched["created_at"], todo["created_at"]);
+        assert!(timestamp(&patched["updated_at"]) > timestamp(&todo["updated_at"]));
+    }
+
+    #[tokio::test]
+    async fn summary_view_omits_text() {
+        let app = api::app();
+        let todo = create_todo(&app, "secret plans").await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos?view=summary")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!([{
+                "id": todo["id"],
+                "completed": false,
+                "completed_at": null,
+                "updated_at": todo["updated_at"],
+            }])
+        );
+
+        // The full view stays the default
+        assert_eq!(list_todos(&app).await, vec![todo]);
+    }
+
+    #[tokio::test]
+    async fn lru_evicts_least_recently_accessed() {
+        let app = api::app_with_config(Config {
+            max_todos: Some(2),
+            eviction_policy: EvictionPolicy::Lru,
+            ..Config::default()
+        });
+
+        let first = create_todo(&app, "first").await;
+        let second = create_todo(&app, "second").await;
+
+        // Touch the first todo so the second becomes the least recently accessed
+        let (status, _) = merge_patch_todo(&app, first["id"].as_str().unwrap(), json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let third = create_todo(&app, "third").await;
+
+        let ids = list_todos(&app)
+            .await
+            .into_iter()
+            .map(|todo| todo["id"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&first["id"]));
+        assert!(ids.contains(&third["id"]));
+        assert!(!ids.contains(&second["id"]));
+    }
+
+    #[tokio::test]
+    async fn lru_ignores_listings() {
+        let app = api::app_with_config(Config {
+            max_todos: Some(2),
+            eviction_policy: EvictionPolicy::Lru,
+            ..Config::default()
+        });
+
+        let first = create_todo(&app, "first").await;
+        let second = create_todo(&app, "second").await;
+
+        let (status, _) = merge_patch_todo(&app, first["id"].as_str().unwrap(), json!({})).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Listing reads the second todo last, but that doesn't make it recent
+        assert_eq!(list_todos(&app).await.len(), 2);
+
+        let third = create_todo(&app, "third").await;
+
+        let ids = list_todos(&app)
+            .await
+            .into_iter()
+            .map(|todo| todo["id"].clone())
+            .collect::<Vec<_>>();
+        assert!(ids.contains(&first["id"]));
+        assert!(ids.contains(&third["id"]));
+        assert!(!ids.contains(&second["id"]));
+    }
+
+    #[tokio::test]
+    async fn quota_rejects_when_full() {
+        let app = api::app_with_config(Config {
+            max_todos: Some(1),
+            ..Config::default()
+        });
+
+        create_todo(&app, "first").await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/todos")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(&json!({ "text": "second" })).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+    }
+
+    #[tokio::test]
+    async fn blank_or_over_long_text_is_rejected_on_create() {
+        let app = api::app();
+
+        let post = |text: String| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/todos")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(&json!({ "text": text })).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+        };
+
+        for text in ["", "  \t\n "] {
+            let response = post(text.to_string()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(
+                body,
+                json!({ "error": { "code": "bad_request", "message": "text must not be empty" } })
+            );
+        }
+
+        // 1000 characters by default
+        let response = post("x".repeat(1001)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], "unprocessable_entity");
+        assert_eq!(body["error"]["message"], "todo text is too long");
+        assert!(list_todos(&app).await.is_empty());
+
+        let response = post("x".repeat(1000)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // Surrounding whitespace is trimmed
+        let todo = create_todo(&app, "  padded \n").await;
+        assert_eq!(todo["text"], "padded");
+    }
+
+    #[tokio::test]
+    async fn over_long_text_is_rejected_by_default() {
+        let app = api::app_with_config(Config {
+            max_text_len: Some(5),
+            ..Config::default()
+        });
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/todos")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(&json!({ "text": "too long" })).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(list_todos(&app).await.is_empty());
+
+        let todo = create_todo(&app, "short").await;
+        let (status, _) = merge_patch_todo(
+            &app,
+            todo["id"].as_str().unwrap(),
+            json!({ "text": "too long" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(list_todos(&app).await[0]["text"], "short");
+
+        // Nothing is too long once the limit is lifted
+        let app = api::app_with_config(Config {
+            max_text_len: None,
+            ..Config::default()
+        });
+        let text = "x".repeat(5000);
+        assert_eq!(create_todo(&app, &text).await["text"], text);
+    }
+
+    #[tokio::test]
+    async fn over_long_text_is_truncated_when_configured() {
+        let app = api::app_with_config(Config {
+            max_text_len: Some(5),
+            text_overflow: TextOverflow::Truncate,
+            ..Config::default()
+        });
+
+        // Truncation counts characters, not bytes
+        let todo = create_todo(&app, "héllo wörld").await;
+        assert_eq!(todo["text"], "héllo");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::PUT)
+                    .uri(format!("/todos/{}", todo["id"].as_str().unwrap()))
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(&json!({ "text": "goodbye" })).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(list_todos(&app).await[0]["text"], "goodb");
+    }
+
+    #[tokio::test]
+    async fn field_naming_is_configurable() {
+        for (field_naming, expected, unexpected) in [
+            (FieldNaming::SnakeCase, "completed_at", "completedAt"),
+            (FieldNaming::CamelCase, "completedAt", "completed_at"),
+        ] {
+            let app = api::app_with_config(Config {
+                field_naming,
+                ..Config::default()
+            });
+
+            let todo = create_todo(&app, "named").await;
+            let (_, todo) = merge_patch_todo(
+                &app,
+                todo["id"].as_str().unwrap(),
+                json!({ "completed": true }),
+            )
+            .await;
+            assert!(todo[expected].is_string(), "{todo}");
+            assert!(todo.get(unexpected).is_none(), "{todo}");
+
+            let listed = list_todos(&app).await;
+            assert!(listed[0][expected].is_string());
+            assert!(listed[0].get(unexpected).is_none());
+
+            let listed = list_todos_msgpack::<
+                std::collections::HashMap<String, serde::de::IgnoredAny>,
+            >(&app)
+            .await;
+            assert!(listed[0].contains_key(expected));
+            assert!(!listed[0].contains_key(unexpected));
+        }
+    }
+
+    #[tokio::test]
+    async fn legacy_mode_renders_booleans_as_integers() {
+        let app = api::app_with_config(Config {
+            legacy_bool_integers: true,
+            ..Config::default()
+        });
+
+        let todo = create_todo(&app, "legacy").await;
+        assert_eq!(todo["completed"], json!(0));
+
+        let (status, todo) = merge_patch_todo(
+            &app,
+            todo["id"].as_str().unwrap(),
+            json!({ "completed": true }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(todo["completed"], json!(1));
+        assert_eq!(list_todos(&app).await[0]["completed"], json!(1));
+
+        #[derive(serde::Deserialize)]
+        struct LegacyTodo {
+            completed: u8,
+        }
+        let listed = list_todos_msgpack::<LegacyTodo>(&app).await;
+        assert_eq!(listed[0].completed, 1);
+
+        // Standard booleans by default
+        let app = api::app();
+        let todo = create_todo(&app, "standard").await;
+        assert_eq!(todo["completed"], json!(false));
+    }
+
+    #[tokio::test]
+    async fn after_cursor_pages_in_position_order() {
+        let app = api::app();
+
+        let first = create_todo(&app, "first").await;
+        create_todo(&app, "second").await;
+        create_todo(&app, "third").await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "/todos?after={}&limit=1",
+                        first["id"].as_str().unwrap()
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let todos: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0]["text"], "second");
+    }
+
+    #[tokio::test]
+    async fn exclusive_query_params_are_rejected() {
+        let app = api::app();
+        let todo = create_todo(&app, "first").await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "/todos?offset=1&after={}",
+                        todo["id"].as_str().unwrap()
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "error": {
+                    "code": "bad_request",
+                    "message": "`offset` and `after` are mutually exclusive"
+                }
+            })
+        );
+    }
+
+    async fn post_empty(app: &Router, uri: &str) -> (StatusCode, Value) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn archive_and_unarchive() {
+        let app = api::app();
+
+        let done = create_todo(&app, "done").await;
+        let open = create_todo(&app, "open").await;
+        let done_id = done["id"].as_str().unwrap();
+        merge_patch_todo(&app, done_id, json!({ "completed": true })).await;
+
+        // Only completed todos can be archived
+        let (status, _) = post_empty(
+            &app,
+            &format!("/todos/{}/archive", open["id"].as_str().unwrap()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, archived) = post_empty(&app, &format!("/todos/{done_id}/archive")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(archived["id"], done["id"]);
+
+        let active = list_todos(&app).await;
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0]["id"], open["id"]);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos/archive")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let archive: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive[0]["id"], done["id"]);
+
+        let (status, unarchived) = post_empty(&app, &format!("/todos/{done_id}/unarchive")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(unarchived["completed"], true);
+        assert_eq!(list_todos(&app).await.len(), 2);
+
+        let (status, _) = post_empty(&app, &format!("/todos/{done_id}/unarchive")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn reorder_sets_position_order() {
+        let app = api::app();
+
+        let first = create_todo(&app, "first").await;
+        let second = create_todo(&app, "second").await;
+        let third = create_todo(&app, "third").await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/todos/reorder")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(
+                            &json!({ "ids": [third["id"], first["id"], second["id"]] }),
+                        )
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos?sort_by=position")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let todos: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        let texts = todos
+            .iter()
+            .map(|todo| todo["text"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["third", "first", "second"]);
+
+        // Ids that aren't stored are rejected
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/todos/reorder")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(&json!({ "ids": [uuid::Uuid::new_v4()] })).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn completion_histogram_buckets() {
+        let completed_on = |date: &str| api::Todo {
+            id: uuid::Uuid::new_v4(),
+            text: date.to_string(),
+            completed: true,
+            completed_at: Some(format!("{date}T12:00:00Z").parse().unwrap()),
+            created_at: Default::default(),
+            updated_at: Default::default(),
+            position: 0,
+        };
+        let todos = [
+            // Monday and Wednesday of one week, Monday of the next
+            completed_on("2024-05-06"),
+            completed_on("2024-05-06"),
+            completed_on("2024-05-08"),
+            completed_on("2024-05-13"),
+            api::Todo {
+                id: uuid::Uuid::new_v4(),
+                text: "open".to_string(),
+                completed: false,
+                completed_at: None,
+                created_at: Default::default(),
+                updated_at: Default::default(),
+                position: 0,
+            },
+        ];
+        let date = |date: &str| date.parse::<chrono::NaiveDate>().unwrap();
+
+        let completions_by_day = api::TodoCounts::scan(todos.iter()).completions_by_day;
+
+        let by_day = api::completion_histogram(&completions_by_day, api::StatsBucket::Day);
+        assert_eq!(
+            by_day,
+            vec![
+                api::CompletionBucket {
+                    start: date("2024-05-06"),
+                    count: 2
+                },
+                api::CompletionBucket {
+                    start: date("2024-05-08"),
+                    count: 1
+                },
+                api::CompletionBucket {
+                    start: date("2024-05-13"),
+                    count: 1
+                },
+            ]
+        );
+
+        let by_week = api::completion_histogram(&completions_by_day, api::StatsBucket::Week);
+        assert_eq!(
+            by_week,
+            vec![
+                api::CompletionBucket {
+                    start: date("2024-05-06"),
+                    count: 3
+                },
+                api::CompletionBucket {
+                    start: date("2024-05-13"),
+                    count: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn store_errors_map_to_status_codes() {
+        use api::StoreError;
+
+        for (error, status) in [
+            (StoreError::NotFound, StatusCode::NOT_FOUND),
+            (StoreError::Conflict, StatusCode::CONFLICT),
+            (StoreError::Full, StatusCode::INSUFFICIENT_STORAGE),
+            (StoreError::EmptyText, StatusCode::BAD_REQUEST),
+            (StoreError::TextTooLong, StatusCode::UNPROCESSABLE_ENTITY),
+            (
+                StoreError::Backend("disk full".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (StoreError::Poisoned, StatusCode::INTERNAL_SERVER_ERROR),
+        ] {
+            let error = api::ApiError::from(error);
+            assert_eq!(error.status(), status);
+            let response = axum::response::IntoResponse::into_response(error);
+            assert_eq!(response.status(), status);
+        }
+    }
+
+    #[tokio::test]
+    async fn created_ids_are_drawn_from_the_pool() {
+        let state = api::AppState::new(Config {
+            id_pool_size: 8,
+            ..Config::default()
+        });
+        let app = api::app_with_state(state.clone());
+
+        let mut ids = std::collections::HashSet::new();
+        // More creates than the pool holds, so some fall back to inline generation
+        for i in 0..32 {
+            let todo = create_todo(&app, &format!("todo {i}")).await;
+            let id: uuid::Uuid = todo["id"].as_str().unwrap().parse().unwrap();
+            assert_eq!(id.get_version(), Some(uuid::Version::Random));
+            assert!(ids.insert(id));
+        }
+
+        // Ids handed out directly are unique as well
+        for _ in 0..32 {
+            assert!(ids.insert(state.ids.next()));
+        }
+    }
+
+    #[tokio::test]
+    async fn running_counts_match_full_scan() {
+        let state = api::AppState::new(Config {
+            max_todos: Some(3),
+            eviction_policy: EvictionPolicy::Lru,
+            ..Config::default()
+        });
+        let app = api::app_with_state(state.clone());
+
+        let first = create_todo(&app, "first").await;
+        let second = create_todo(&app, "second").await;
+        let third = create_todo(&app, "third").await;
+
+        for (todo, patch) in [
+            (&first, json!({ "completed": true })),
+            (&second, json!({ "completed": true })),
+            (&second, json!({ "completed": false })),
+            (&third, json!({ "completed": true, "text": "third, done" })),
+        ] {
+            let (status, _) = merge_patch_todo(&app, todo["id"].as_str().unwrap(), patch).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::DELETE)
+                    .uri(format!("/todos/{}", third["id"].as_str().unwrap()))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // Fills the store, then evicts the least recently used todo
+        create_todo(&app, "fourth").await;
+        create_todo(&app, "fifth").await;
+
+        let counts = state.counters.snapshot();
+        let scanned = api::TodoCounts::scan(state.db.read().unwrap().values());
+        assert_eq!(counts, scanned);
+        assert_eq!(counts.total, 3);
+    }
+
+    #[tokio::test]
+    async fn todos_stats_counts_completions() {
+        let app = api::app();
+        let todo = create_todo(&app, "done").await;
+        create_todo(&app, "open").await;
+        merge_patch_todo(
+            &app,
+            todo["id"].as_str().unwrap(),
+            json!({ "completed": true }),
+        )
+        .await;
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/todos/stats?bucket=week")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["total"], 2);
+        assert_eq!(body["completed"], 1);
+        assert_eq!(body["completions"].as_array().unwrap().len(), 1);
+        assert_eq!(body["completions"][0]["count"], 1);
+    }
+
+    async fn preflight_version_header(middleware: Vec<Middleware>) -> Option<http::HeaderValue> {
+        let app = api::app_with_config(Config {
+            cors: Some(CorsConfig {
+                allowed_origins: vec!["https://app.example.com".to_string()],
+                ..CorsConfig::default()
+            }),
+            middleware,
+            ..Config::default()
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::OPTIONS)
+                    .uri("/todos")
+                    .header(http::header::ORIGIN, "https://app.example.com")
+                    .header(http::header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        response.headers().get(api::APP_VERSION_HEADER).cloned()
+    }
+
+    #[tokio::test]
+    async fn middleware_order_is_configurable() {
+        // CORS answers preflights itself, so only layers outside it touch the response
+        let version =
+            preflight_version_header(vec![Middleware::AppVersion, Middleware::Cors]).await;
+        assert!(version.is_some());
+
+        let version =
+            preflight_version_header(vec![Middleware::Cors, Middleware::AppVersion]).await;
+        assert!(version.is_none());
+    }
+
+    #[tokio::test]
+    async fn user_agent_is_required_when_enabled() {
+        let app = api::app_with_config(Config {
+            middleware: vec![Middleware::RequireUserAgent],
+            ..Config::default()
+        });
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos")
+                    .header(http::header::USER_AGENT, "todo-client/1.0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for user_agent in [None, Some("  ")] {
+            let mut request = Request::builder().uri("/todos");
+            if let Some(user_agent) = user_agent {
+                request = request.header(http::header::USER_AGENT, user_agent);
+            }
+            let response = app
+                .clone()
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        // Off by default
+        let response = api::app()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn racing_delete_and_update_never_resurrects() {
+        let app = api::app();
+        let todo = create_todo(&app, "contended").await;
+        let id = todo["id"].as_str().unwrap().to_string();
+
+        let mut tasks = Vec::new();
+        for i in 0..20 {
+            let app = app.clone();
+            let id = id.clone();
+            tasks.push(tokio::spawn(async move {
+                if i == 10 {
+                    let response = app
+                        .oneshot(
+                            Request::builder()
+                                .method(http::Method::DELETE)
+                                .uri(format!("/todos/{id}"))
+                                .body(Body::empty())
+                                .unwrap(),
+                        )
+                        .await
+                        .unwrap();
+                    response.status()
+                } else {
+                    merge_patch_todo(&app, &id, json!({ "text": format!("update {i}") }))
+                        .await
+                        .0
+                }
+            }));
+        }
+
+        for task in tasks {
+            let status = task.await.unwrap();
+            assert!(
+                status == StatusCode::OK
+                    || status == StatusCode::NO_CONTENT
+                    || status == StatusCode::NOT_FOUND
+            );
+        }
+
+        // Once deleted, the todo stays deleted
+        assert!(list_todos(&app).await.is_empty());
+        let (status, _) = merge_patch_todo(&app, &id, json!({ "completed": true })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(list_todos(&app).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn todos_msgpack_matches_json() {
+        let app = api::app();
+        create_todo(&app, "first").await;
+        create_todo(&app, "second").await;
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos")
+                    .header(http::header::ACCEPT, api::MSGPACK_CONTENT_TYPE)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[http::header::CONTENT_TYPE],
+            api::MSGPACK_CONTENT_TYPE
+        );
 
-        if let Some(completed) = input.completed {
-            todo.completed = completed;
-        }
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let from_msgpack: Vec<api::Todo> = rmp_serde::from_slice(&body).unwrap();
 
-        db.write().unwrap().insert(todo.id, todo.clone());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/todos")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let from_json: Vec<api::Todo> = serde_json::from_slice(&body).unwrap();
 
-        Ok(Json(todo))
+        assert_eq!(from_msgpack.len(), 2);
+        assert_eq!(from_msgpack, from_json);
     }
 
-    /// Delete todo by id
-    ///
-    /// Delete todo from database by todo id
-    #[utoipa::path(
-    delete,
-    path = "/todos/{id}",
-    responses(
-        (status = NO_CONTENT, description = "Todo deleted successfully"),
-        (status = NOT_FOUND, description = "Todo was not found")
-    ),
-    params(
-        ("id" = Path<Uuid>, Path, description = "Todo database id to delete Todo for"),
-    )
-    )]
-    async fn todos_delete(Path(id): Path<Uuid>, State(db): State<Db>) -> impl IntoResponse {
-        if db.write().unwrap().remove(&id).is_some() {
-            StatusCode::NO_CONTENT
-        } else {
-            StatusCode::NOT_FOUND
-        }
-    }
+    #[tokio::test]
+    async fn todos_filter_by_ids() {
+        let app = api::app();
+        let first = create_todo(&app, "first").await;
+        let second = create_todo(&app, "second").await;
+        create_todo(&app, "third").await;
+        let missing = uuid::Uuid::new_v4();
 
-    type Db = Arc<RwLock<HashMap<Uuid, Todo>>>;
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!(
+                        "/todos?id={}&id={}&id={missing}",
+                        first["id"].as_str().unwrap(),
+                        second["id"].as_str().unwrap(),
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
 
-    #[derive(Debug, Serialize, Clone, ToSchema)]
-    struct Todo {
-        id: Uuid,
-        text: String,
-        completed: bool,
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let todos: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        let ids = todos.iter().map(|todo| &todo["id"]).collect::<Vec<_>>();
+        assert_eq!(ids.len(), 2);
+        assert!(ids.contains(&&first["id"]));
+        assert!(ids.contains(&&second["id"]));
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::{
-        body::Body,
-        extract::connect_info::MockConnectInfo,
-        http::{self, Request, StatusCode},
-    };
-    use http_body_util::BodyExt; // for `collect`
-    use serde_json::{json, Value};
-    use std::net::SocketAddr;
-    use tokio::net::TcpListener;
-    use tower::{Service, ServiceExt}; // for `call`, `oneshot`, and `ready`
 
     #[tokio::test]
-    async fn todos_get() {
+    async fn app_version_header() {
         let app = api::app();
 
-        // `Router` implements `tower::Service<Request<Body>>` so we can
-        // call it like any tower service, no need to run an HTTP server.
         let response = app
             .oneshot(
                 Request::builder()
-                    .method(http::Method::GET)
                     .uri("/todos")
                     .body(Body::empty())
                     .unwrap(),
@@ -302,76 +4755,190 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
+        let version = response
+            .headers()
+            .get(api::APP_VERSION_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(version.starts_with(&format!("{}+", env!("CARGO_PKG_VERSION"))));
+        assert_eq!(version, build_info::app_version());
+    }
 
-        let body = response.into_body().collect().await.unwrap().to_bytes();
-        assert_eq!(&body[..], b"[]");
+    #[tokio::test]
+    async fn trailing_data_after_json_body_is_rejected() {
+        let app = api::app();
+        let todo = create_todo(&app, "x").await;
+
+        for (method, uri, content_type) in [
+            (
+                http::Method::POST,
+                "/todos".to_string(),
+                mime::APPLICATION_JSON.as_ref(),
+            ),
+            (
+                http::Method::PATCH,
+                format!("/todos/{}", todo["id"].as_str().unwrap()),
+                api::MERGE_PATCH_CONTENT_TYPE,
+            ),
+        ] {
+            let response = app
+                .clone()
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .header(http::header::CONTENT_TYPE, content_type)
+                        .body(Body::from(r#"{"text":"x"} garbage"#))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        assert_eq!(list_todos(&app).await, vec![todo]);
+
+        // Trailing data is ignored when strictness is turned off
+        let app = api::app_with_config(Config {
+            strict_json: false,
+            ..Config::default()
+        });
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/todos")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(r#"{"text":"x"} garbage"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
     }
 
     #[tokio::test]
-    async fn todos_get_plus_query() {
+    async fn missing_todo_errors_have_a_json_body() {
         let app = api::app();
+        let uri = format!("/todos/{}", uuid::Uuid::new_v4());
+
+        for request in [
+            Request::builder()
+                .method(http::Method::PUT)
+                .uri(&uri)
+                .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                .body(Body::from(
+                    serde_json::to_vec(&json!({ "completed": true })).unwrap(),
+                ))
+                .unwrap(),
+            Request::builder()
+                .method(http::Method::DELETE)
+                .uri(&uri)
+                .body(Body::empty())
+                .unwrap(),
+        ] {
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(
+                body,
+                json!({ "error": { "code": "not_found", "message": "todo was not found" } })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn get_one_todo() {
+        let app = api::app();
+        let todo = create_todo(&app, "fetch me").await;
 
-        // `Router` implements `tower::Service<Request<Body>>` so we can
-        // call it like any tower service, no need to run an HTTP server.
         let response = app
             .clone()
             .oneshot(
                 Request::builder()
-                    .method(http::Method::GET)
-                    .uri("/todos?offset=0&limit=0")
+                    .uri(format!("/todos/{}", todo["id"].as_str().unwrap()))
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
-
         assert_eq!(response.status(), StatusCode::OK);
-
         let body = response.into_body().collect().await.unwrap().to_bytes();
-        assert_eq!(&body[..], b"[]");
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, todo);
 
         let response = app
-            .clone()
             .oneshot(
                 Request::builder()
-                    .method(http::Method::GET)
-                    .uri("/todos?offset=0&limit=2")
+                    .uri(format!("/todos/{}", uuid::Uuid::new_v4()))
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 
-        assert_eq!(response.status(), StatusCode::OK);
+    #[tokio::test]
+    async fn openapi_spec_lists_configured_servers() {
+        let fetch_spec = |config: Config| async move {
+            let response = api::app_with_config(config)
+                .oneshot(
+                    Request::builder()
+                        .uri("/api-docs/openapi.json")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            serde_json::from_slice::<Value>(&body).unwrap()
+        };
 
-        let body = response.into_body().collect().await.unwrap().to_bytes();
-        assert_eq!(&body[..], b"[]");
+        let spec = fetch_spec(Config {
+            openapi_servers: vec![
+                "https://todos.example.com".to_string(),
+                "https://staging.todos.example.com".to_string(),
+            ],
+            ..Config::default()
+        })
+        .await;
+        assert_eq!(
+            spec["servers"],
+            json!([
+                { "url": "https://todos.example.com" },
+                { "url": "https://staging.todos.example.com" }
+            ])
+        );
+
+        let spec = fetch_spec(Config::default()).await;
+        assert!(spec.get("servers").is_none());
     }
 
     #[tokio::test]
-    async fn json() {
+    async fn version_reports_build_metadata() {
         let app = api::app();
 
         let response = app
             .oneshot(
                 Request::builder()
-                    .method(http::Method::POST)
-                    .uri("/json")
-                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
-                    .body(Body::from(
-                        serde_json::to_vec(&json!([1, 2, 3, 4])).unwrap(),
-                    ))
+                    .uri("/version")
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
-
         assert_eq!(response.status(), StatusCode::OK);
 
         let body = response.into_body().collect().await.unwrap().to_bytes();
         let body: Value = serde_json::from_slice(&body).unwrap();
-        assert_eq!(body, json!({ "data": [1, 2, 3, 4] }));
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(body["commit"], build_info::GIT_HASH);
+        assert!(body["rustc"].as_str().unwrap().starts_with("rustc "));
     }
 
     #[tokio::test]
@@ -455,6 +5022,333 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    async fn post_chunked(addr: SocketAddr, chunks: &[&[u8]]) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        let mut request = b"POST /todos HTTP/1.1\r\nHost: localhost\r\n\
+            Content-Type: application/json\r\nTransfer-Encoding: chunked\r\n\
+            Connection: close\r\n\r\n"
+            .to_vec();
+        for chunk in chunks {
+            request.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            request.extend_from_slice(chunk);
+            request.extend_from_slice(b"\r\n");
+        }
+        request.extend_from_slice(b"0\r\n\r\n");
+        stream.write_all(&request).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn chunked_body_respects_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let app = api::app_with_config(Config {
+                max_body_bytes: 64,
+                ..Config::default()
+            });
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let response = post_chunked(addr, &[b"{\"text\":", b"\"short\"}"]).await;
+        assert!(response.starts_with("HTTP/1.1 201"), "{response}");
+
+        let long_text = "x".repeat(100);
+        let response = post_chunked(addr, &[b"{\"text\":\"", long_text.as_bytes(), b"\"}"]).await;
+        assert!(response.starts_with("HTTP/1.1 413"), "{response}");
+    }
+
+    // Send only the head of a request, then read the first response head within a second
+    async fn send_head(stream: &mut tokio::net::TcpStream, head: &str) -> String {
+        stream.write_all(head.as_bytes()).await.unwrap();
+
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        while !response.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read =
+                tokio::time::timeout(std::time::Duration::from_secs(1), stream.read(&mut buf))
+                    .await
+                    .expect("server stalled instead of answering the request head")
+                    .unwrap();
+            if read == 0 {
+                break;
+            }
+            response.extend_from_slice(&buf[..read]);
+        }
+        String::from_utf8(response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn expect_continue_is_answered_early() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let app = api::app_with_config(Config {
+                max_body_bytes: 64,
+                ..Config::default()
+            });
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        // Over the limit: rejected without the body ever being sent
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let response = send_head(
+            &mut stream,
+            "POST /todos HTTP/1.1\r\nHost: localhost\r\n\
+            Content-Type: application/json\r\nContent-Length: 1000\r\n\
+            Expect: 100-continue\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 413"), "{response}");
+
+        // Within the limit: told to continue, then the upload succeeds
+        let body = r#"{"text":"short"}"#;
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let response = send_head(
+            &mut stream,
+            &format!(
+                "POST /todos HTTP/1.1\r\nHost: localhost\r\n\
+                Content-Type: application/json\r\nContent-Length: {}\r\n\
+                Expect: 100-continue\r\n\r\n",
+                body.len()
+            ),
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 100"), "{response}");
+        let response = send_head(&mut stream, body).await;
+        assert!(response.starts_with("HTTP/1.1 201"), "{response}");
+
+        // Expectations other than 100-continue can't be met
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let response = send_head(
+            &mut stream,
+            "POST /todos HTTP/1.1\r\nHost: localhost\r\n\
+            Content-Length: 2\r\nExpect: something-else\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 417"), "{response}");
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn grpc_create_then_list_matches_rest() {
+        use grpc::proto::{todos_client::TodosClient, CreateTodoRequest, ListTodosRequest};
+
+        let state = api::AppState::new(Config::default());
+        let rest = api::app_with_state(state.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .add_service(grpc::server(state))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+                .await
+                .unwrap();
+        });
+
+        let mut client = TodosClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+        let created = client
+            .create(CreateTodoRequest {
+                text: "from grpc".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(created.text, "from grpc");
+
+        let listed = client
+            .list(ListTodosRequest::default())
+            .await
+            .unwrap()
+            .into_inner()
+            .todos;
+        assert_eq!(listed, vec![created.clone()]);
+
+        let from_rest = list_todos(&rest).await;
+        assert_eq!(from_rest.len(), 1);
+        assert_eq!(from_rest[0]["id"], created.id);
+        assert_eq!(from_rest[0]["text"], "from grpc");
+    }
+
+    #[tokio::test]
+    async fn rpc_batch_returns_results_in_order() {
+        let app = api::app();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/rpc")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(
+                        serde_json::to_vec(&json!([
+                            {
+                                "jsonrpc": "2.0",
+                                "method": "todos.create",
+                                "params": { "text": "via rpc" },
+                                "id": 1
+                            },
+                            { "jsonrpc": "2.0", "method": "todos.list", "id": 2 },
+                            { "jsonrpc": "2.0", "method": "todos.unknown", "id": 3 }
+                        ]))
+                        .unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let responses = body.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["text"], "via rpc");
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["result"], json!([responses[0]["result"]]));
+        assert_eq!(responses[2]["id"], 3);
+        assert_eq!(responses[2]["error"]["code"], -32601);
+    }
+
+    #[tokio::test]
+    async fn rpc_batch_over_max_size_is_rejected() {
+        let state = api::AppState::new(Config {
+            max_batch_size: Some(2),
+            ..Config::default()
+        });
+        let app = api::app_with_state(state.clone());
+
+        let post_batch = |size: usize| {
+            let calls = (0..size)
+                .map(|id| {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "method": "todos.create",
+                        "params": { "text": format!("todo {id}") },
+                        "id": id
+                    })
+                })
+                .collect::<Vec<_>>();
+            app.clone().oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/rpc")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(serde_json::to_vec(&calls).unwrap()))
+                    .unwrap(),
+            )
+        };
+
+        let response = post_batch(3).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["code"], -32600);
+        // None of the calls ran
+        assert!(state.db.read().unwrap().is_empty());
+
+        let response = post_batch(2).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.as_array().unwrap().len(), 2);
+        assert_eq!(state.db.read().unwrap().len(), 2);
+    }
+
+    async fn post_rpc(app: &Router, body: Value) -> Value {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(http::Method::POST)
+                    .uri("/rpc")
+                    .header(http::header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+                    .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn rpc_list_pages_like_rest() {
+        let clock = rest_actuator::api::MockClock::new(std::time::UNIX_EPOCH);
+        let app = api::app_with_state(api::AppState {
+            clock: std::sync::Arc::new(clock.clone()),
+            ..api::AppState::new(Config::default())
+        });
+        for i in 0..8 {
+            create_todo(&app, &format!("todo {i}")).await;
+            clock.advance(std::time::Duration::from_secs(1));
+        }
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/todos?offset=2&limit=3")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let rest: Value = serde_json::from_slice(&body).unwrap();
+
+        let rpc = post_rpc(
+            &app,
+            json!({
+                "jsonrpc": "2.0",
+                "method": "todos.list",
+                "params": { "offset": 2, "limit": 3 },
+                "id": 1
+            }),
+        )
+        .await;
+        assert_eq!(rpc["result"], rest);
+        let texts = rest
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|todo| todo["text"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["todo 2", "todo 3", "todo 4"]);
+    }
+
+    #[tokio::test]
+    async fn rpc_invalid_request_echoes_id() {
+        let app = api::app();
+
+        let response = post_rpc(
+            &app,
+            json!({ "jsonrpc": "1.0", "method": "todos.list", "id": 7 }),
+        )
+        .await;
+        assert_eq!(response["error"]["code"], -32600);
+        assert_eq!(response["id"], 7);
+
+        // Without a usable id there is nothing to echo
+        let response = post_rpc(&app, json!({ "method": "todos.list", "id": [7] })).await;
+        assert_eq!(response["error"]["code"], -32600);
+        assert_eq!(response["id"], Value::Null);
+    }
+
     // Here we're calling `/requires-connect-info` which requires `ConnectInfo`
     //
     // That is normally set with `Router::into_make_service_with_connect_info` but we can't easily
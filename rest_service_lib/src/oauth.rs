@@ -1,56 +1,787 @@
-mod oauth {
-    use axum::extract::{cookie::Key, FromRef};
-    use axum::{routing::get, Router};
-    use reqwest::Client as ReqwestClient;
-    use sqlx::PgPool;
-
-    #[derive(Clone)]
-    pub struct AppState {
-        db: PgPool,
-        ctx: ReqwestClient,
-        key: Key,
-    }
-
-    // implementing FromRef is required here so we can extract substate in Axum
-    // read more here: https://docs.rs/axum/latest/axum/extract/trait.FromRef.html
-    impl FromRef<AppState> for Key {
-        fn from_ref(state: &AppState) -> Self {
-            state.key.clone()
+use crate::api::ApiError;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
+    Json, Router,
+};
+use axum_extra::extract::cookie::{Cookie, Key, PrivateCookieJar, SameSite};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use oauth2::{
+    basic::BasicClient, reqwest::async_http_client, AuthUrl, AuthorizationCode, ClientId,
+    ClientSecret, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+    TokenResponse, TokenUrl,
+};
+use reqwest::Client as ReqwestClient;
+use rest_actuator::api::{SharedClock, SystemClock};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// CSRF `state` and PKCE verifier issued by the login route, checked by the callback
+const STATE_COOKIE: &str = "oauth_state";
+// The signed in user, see `Session`
+pub(crate) const SESSION_COOKIE: &str = "session";
+// How long a sign in lasts
+const SESSION_TTL_HOURS: i64 = 24;
+
+// Endpoints and credentials of one OAuth provider
+#[derive(Debug, Clone)]
+pub(crate) struct ProviderConfig {
+    pub(crate) client_id: String,
+    pub(crate) client_secret: String,
+    pub(crate) auth_url: String,
+    pub(crate) token_url: String,
+    pub(crate) userinfo_url: String,
+}
+
+// The supported providers, served at `/auth/<name>`
+#[derive(Debug, Clone)]
+pub(crate) enum OAuthProvider {
+    Google(ProviderConfig),
+    GitHub(ProviderConfig),
+}
+
+impl OAuthProvider {
+    pub(crate) fn google(client_id: String, client_secret: String) -> Self {
+        OAuthProvider::Google(ProviderConfig {
+            client_id,
+            client_secret,
+            auth_url: "https://accounts.google.com/o/oauth2/v2/auth".to_string(),
+            token_url: "https://oauth2.googleapis.com/token".to_string(),
+            userinfo_url: "https://openidconnect.googleapis.com/v1/userinfo".to_string(),
+        })
+    }
+
+    pub(crate) fn github(client_id: String, client_secret: String) -> Self {
+        OAuthProvider::GitHub(ProviderConfig {
+            client_id,
+            client_secret,
+            auth_url: "https://github.com/login/oauth/authorize".to_string(),
+            token_url: "https://github.com/login/oauth/access_token".to_string(),
+            userinfo_url: "https://api.github.com/user".to_string(),
+        })
+    }
+
+    // Path segment of the provider's routes
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            OAuthProvider::Google(_) => "google",
+            OAuthProvider::GitHub(_) => "github",
         }
     }
 
-    async fn hello_world() -> &'static str {
-        "Hello world!"
+    fn config(&self) -> &ProviderConfig {
+        match self {
+            OAuthProvider::Google(config) | OAuthProvider::GitHub(config) => config,
+        }
     }
 
-    #[shuttle_runtime::main]
-    async fn axum(
-        #[shuttle_shared_db::Postgres] db: PgPool,
-        #[shuttle_secrets::Secrets] secrets: SecretStore,
-    ) -> shuttle_axum::ShuttleAxum {
-        sqlx::migrate!()
-            .run(&db)
-            .await
-            .expect("Failed migrations :(");
+    fn scopes(&self) -> &'static [&'static str] {
+        match self {
+            OAuthProvider::Google(_) => &["openid", "email", "profile"],
+            OAuthProvider::GitHub(_) => &["read:user", "user:email"],
+        }
+    }
+
+    // Map the provider's userinfo JSON onto `UserInfo`, `None` without a user id
+    pub(crate) fn user_info(&self, profile: &Value) -> Option<UserInfo> {
+        let text = |key: &str| profile[key].as_str().map(str::to_string);
+        let id = match self {
+            OAuthProvider::Google(_) => text("sub")?,
+            // GitHub ids are numbers
+            OAuthProvider::GitHub(_) => profile["id"].as_u64()?.to_string(),
+        };
+        let name = match self {
+            OAuthProvider::Google(_) => text("name"),
+            OAuthProvider::GitHub(_) => text("name").or_else(|| text("login")),
+        };
+        Some(UserInfo {
+            id,
+            email: text("email"),
+            name,
+        })
+    }
+}
 
-        // Getting secrets from our SecretsStore - safe to unwrap as they're required for the app to work
-        let oauth_id = secrets.get("GOOGLE_OAUTH_CLIENT_ID").unwrap();
-        let oauth_secret = secrets.get("GOOGLE_OAUTH_CLIENT_SECRET").unwrap();
+// The user's profile, whichever provider they signed in with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct UserInfo {
+    pub(crate) id: String,
+    pub(crate) email: Option<String>,
+    pub(crate) name: Option<String>,
+}
 
-        let ctx = ReqwestClient::new();
+#[derive(Clone)]
+pub(crate) struct OAuthState {
+    // Configured providers with their clients, by name
+    providers: Arc<HashMap<&'static str, (OAuthProvider, BasicClient)>>,
+    ctx: ReqwestClient,
+    key: Key,
+    // Decides when sessions expire, see `with_clock`
+    clock: SharedClock,
+    // Whether cookies are only sent back over HTTPS, as when the redirect base URL is
+    secure_cookies: bool,
+}
 
-        let state = AppState {
-            db,
-            ctx,
+// implementing FromRef is required here so we can extract substate in Axum
+// read more here: https://docs.rs/axum/latest/axum/extract/trait.FromRef.html
+impl FromRef<OAuthState> for Key {
+    fn from_ref(state: &OAuthState) -> Self {
+        state.key.clone()
+    }
+}
+
+impl FromRef<OAuthState> for SharedClock {
+    fn from_ref(state: &OAuthState) -> Self {
+        state.clock.clone()
+    }
+}
+
+impl OAuthState {
+    // Serve `providers`, each redirecting back to `/auth/<name>/callback` under
+    // `redirect_base_url`
+    pub(crate) fn new(
+        providers: Vec<OAuthProvider>,
+        redirect_base_url: &str,
+    ) -> Result<Self, oauth2::url::ParseError> {
+        let redirect_base_url = redirect_base_url.trim_end_matches('/');
+        let secure_cookies = oauth2::url::Url::parse(redirect_base_url)?.scheme() == "https";
+        let providers = providers
+            .into_iter()
+            .map(|provider| {
+                let redirect_url = format!("{redirect_base_url}/auth/{}/callback", provider.name());
+                let client = build_oauth_client(provider.config(), &redirect_url)?;
+                Ok((provider.name(), (provider, client)))
+            })
+            .collect::<Result<HashMap<_, _>, oauth2::url::ParseError>>()?;
+
+        Ok(OAuthState {
+            providers: Arc::new(providers),
+            ctx: ReqwestClient::new(),
+            // Cookies only need to outlive the process, like the todos
             key: Key::generate(),
+            clock: Arc::new(SystemClock),
+            secure_cookies,
+        })
+    }
+
+    // Time sessions with `clock` rather than the system clock
+    pub(crate) fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+// Build an oauth client that can interface with any OAuth service, depending on the URLs
+// passed in, see https://docs.rs/oauth2/latest/oauth2/struct.Client.html#method.new
+pub(crate) fn build_oauth_client(
+    config: &ProviderConfig,
+    redirect_url: &str,
+) -> Result<BasicClient, oauth2::url::ParseError> {
+    Ok(BasicClient::new(
+        ClientId::new(config.client_id.clone()),
+        Some(ClientSecret::new(config.client_secret.clone())),
+        AuthUrl::new(config.auth_url.clone())?,
+        Some(TokenUrl::new(config.token_url.clone())?),
+    )
+    .set_redirect_uri(RedirectUrl::new(redirect_url.to_string())?))
+}
+
+// `GET /auth/:provider`, its callback `GET /auth/:provider/callback` and `GET /auth/me`
+pub(crate) fn routes(state: OAuthState) -> Router {
+    Router::new()
+        .route("/auth/me", get(me))
+        .route("/auth/:provider", get(login))
+        .route("/auth/:provider/callback", get(callback))
+        .with_state(state)
+}
+
+// A signed in user, kept in a private cookie so clients can neither read nor forge it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Session {
+    pub(crate) user_id: String,
+    pub(crate) expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    // A session signed in at `now`
+    pub(crate) fn new(user_id: String, now: DateTime<Utc>) -> Self {
+        Session {
+            user_id,
+            expires_at: now + ChronoDuration::hours(SESSION_TTL_HOURS),
+        }
+    }
+}
+
+// Store `session` in the jar, replacing any previous one. The cookie itself lasts for the
+// browser session; `expires_at` is enforced when it is read back. A `secure` cookie is
+// only sent back over HTTPS
+pub(crate) fn set_session(
+    jar: PrivateCookieJar,
+    session: &Session,
+    secure: bool,
+) -> PrivateCookieJar {
+    let value = serde_json::to_string(session).expect("sessions serialize to JSON");
+    let cookie = Cookie::build((SESSION_COOKIE, value))
+        .path("/")
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Lax)
+        .build();
+    jar.add(cookie)
+}
+
+// Rejects with `401` unless the request carries an unexpired session. A tampered cookie
+// fails to decrypt and counts as missing
+#[async_trait]
+impl<S> FromRequestParts<S> for Session
+where
+    Key: FromRef<S>,
+    SharedClock: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let jar = PrivateCookieJar::<Key>::from_request_parts(parts, state)
+            .await
+            .unwrap_or_else(|never| match never {});
+        let session = jar
+            .get(SESSION_COOKIE)
+            .and_then(|cookie| serde_json::from_str::<Session>(cookie.value()).ok())
+            .ok_or(ApiError::Unauthorized)?;
+        if session.expires_at <= DateTime::<Utc>::from(SharedClock::from_ref(state).now()) {
+            return Err(ApiError::Unauthorized);
+        }
+        Ok(session)
+    }
+}
+
+async fn me(session: Session) -> Json<Session> {
+    Json(session)
+}
+
+// What the login route hands over to the callback, see `STATE_COOKIE`
+#[derive(Debug, Serialize, Deserialize)]
+struct PendingLogin {
+    state: String,
+    // Optional so a cookie without one is rejected as such rather than as a forgery
+    #[serde(default)]
+    verifier: Option<String>,
+}
+
+impl PendingLogin {
+    fn to_cookie(&self, secure: bool) -> Cookie<'static> {
+        state_cookie(
+            serde_json::to_string(self).expect("pending login serializes to JSON"),
+            secure,
+        )
+    }
+
+    fn from_jar(jar: &PrivateCookieJar) -> Option<Self> {
+        jar.get(STATE_COOKIE)
+            .and_then(|cookie| serde_json::from_str(cookie.value()).ok())
+    }
+}
+
+fn state_cookie(value: String, secure: bool) -> Cookie<'static> {
+    Cookie::build((STATE_COOKIE, value))
+        .path("/auth")
+        .http_only(true)
+        .secure(secure)
+        .same_site(SameSite::Lax)
+        .build()
+}
+
+// Redirect to the provider's consent screen, remembering the CSRF `state` in a private
+// cookie so the callback can tell its own redirects from forged ones. The PKCE verifier
+// rides along in the same cookie, so an intercepted code is useless on its own
+async fn login(
+    State(state): State<OAuthState>,
+    Path(provider): Path<String>,
+    jar: PrivateCookieJar,
+) -> Response {
+    let Some((provider, client)) = state.providers.get(provider.as_str()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let (authorize_url, csrf_state) = client
+        .authorize_url(CsrfToken::new_random)
+        .set_pkce_challenge(pkce_challenge)
+        .add_scopes(
+            provider
+                .scopes()
+                .iter()
+                .map(|scope| Scope::new(scope.to_string())),
+        )
+        .url();
+
+    let pending = PendingLogin {
+        state: csrf_state.secret().clone(),
+        verifier: Some(pkce_verifier.secret().clone()),
+    };
+    let jar = jar.add(pending.to_cookie(state.secure_cookies));
+    (jar, Redirect::to(authorize_url.as_str())).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct CallbackQuery {
+    code: String,
+    state: String,
+}
+
+// Exchange the authorization code for tokens, fetch the user's profile and keep the
+// session in a private cookie, then show the signed in user at `/auth/me`
+async fn callback(
+    State(state): State<OAuthState>,
+    Path(provider): Path<String>,
+    jar: PrivateCookieJar,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Response, ApiError> {
+    let Some((provider, client)) = state.providers.get(provider.as_str()) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    // The state is single use, whatever the outcome
+    let pending = PendingLogin::from_jar(&jar);
+    let jar = jar.remove(state_cookie(String::new(), state.secure_cookies));
+    let Some(pending) = pending.filter(|pending| pending.state == query.state) else {
+        return Err(ApiError::BadRequest(
+            "OAuth state does not match the one issued".to_string(),
+        ));
+    };
+    let Some(verifier) = pending.verifier else {
+        return Err(ApiError::BadRequest(
+            "OAuth PKCE verifier is missing".to_string(),
+        ));
+    };
+
+    let token = client
+        .exchange_code(AuthorizationCode::new(query.code))
+        .set_pkce_verifier(PkceCodeVerifier::new(verifier))
+        .request_async(async_http_client)
+        .await
+        .map_err(|err| {
+            tracing::error!(
+                "OAuth token exchange with {} failed: {err}",
+                provider.name()
+            );
+            ApiError::Internal
+        })?;
+
+    let profile = state
+        .ctx
+        .get(&provider.config().userinfo_url)
+        .bearer_auth(token.access_token().secret())
+        // Required by the GitHub API
+        .header(header::USER_AGENT, crate::build_info::NAME)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|err| {
+            tracing::error!(
+                "failed to fetch the {} user profile: {err}",
+                provider.name()
+            );
+            ApiError::Internal
+        })?
+        .bytes()
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|body| serde_json::from_slice::<Value>(&body).map_err(|err| err.to_string()))
+        .map_err(|err| {
+            tracing::error!("invalid {} user profile: {err}", provider.name());
+            ApiError::Internal
+        })?;
+    let user_info = provider.user_info(&profile).ok_or_else(|| {
+        tracing::error!("{} user profile has no id", provider.name());
+        ApiError::Internal
+    })?;
+    tracing::debug!(provider = provider.name(), id = %user_info.id, "signed in");
+
+    let user_id = format!("{}:{}", provider.name(), user_info.id);
+    let jar = set_session(
+        jar,
+        &Session::new(user_id, state.clock.now().into()),
+        state.secure_cookies,
+    );
+    Ok((jar, Redirect::to("/auth/me")).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+        response::IntoResponse,
+        routing::post,
+        Form,
+    };
+    use http_body_util::BodyExt; // for `collect`
+    use rest_actuator::api::MockClock;
+    use serde_json::json;
+    use tokio::net::TcpListener;
+    use tower::ServiceExt;
+
+    // Serve `router` on an ephemeral port, returning its base URL
+    async fn serve(router: Router) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    // A provider whose token endpoint answers with `token_status`, or `400` when the
+    // exchange lacks a PKCE verifier
+    async fn oauth_state(token_status: StatusCode) -> OAuthState {
+        let provider = serve(
+            Router::new()
+                .route(
+                    "/token",
+                    post(
+                        move |Form(form): Form<HashMap<String, String>>| async move {
+                            let status = if form.contains_key("code_verifier") {
+                                token_status
+                            } else {
+                                StatusCode::BAD_REQUEST
+                            };
+                            let body = json!({ "access_token": "token", "token_type": "bearer" });
+                            (status, Json(body))
+                        },
+                    ),
+                )
+                .route(
+                    "/userinfo",
+                    get(|| async {
+                        Json(json!({ "sub": "1234", "email": "ada@example.com", "name": "Ada" }))
+                    }),
+                ),
+        )
+        .await;
+
+        let google = OAuthProvider::Google(ProviderConfig {
+            client_id: "client-id".to_string(),
+            client_secret: "client-secret".to_string(),
+            auth_url: format!("{provider}/authorize"),
+            token_url: format!("{provider}/token"),
+            userinfo_url: format!("{provider}/userinfo"),
+        });
+        OAuthState::new(vec![google], "http://localhost:3000").unwrap()
+    }
+
+    async fn app(token_status: StatusCode) -> Router {
+        routes(oauth_state(token_status).await)
+    }
+
+    // Start a login, returning the issued state and the cookie holding it
+    async fn login(app: &Router) -> (String, String) {
+        let response = app
+            .clone()
+            .oneshot(Request::get("/auth/google").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+        let location = response.headers()[header::LOCATION].to_str().unwrap();
+        let location = oauth2::url::Url::parse(location).unwrap();
+        assert_eq!(location.path(), "/authorize");
+        assert!(location
+            .query_pairs()
+            .any(|(key, value)| key == "redirect_uri"
+                && value == "http://localhost:3000/auth/google/callback"));
+        assert!(location
+            .query_pairs()
+            .any(|(key, value)| key == "code_challenge_method" && value == "S256"));
+        let state = location
+            .query_pairs()
+            .find(|(key, _)| key == "state")
+            .map(|(_, value)| value.into_owned())
+            .unwrap();
+
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap().to_string();
+        (state, cookie)
+    }
+
+    async fn callback(app: &Router, state: &str, cookie: &str) -> axum::response::Response {
+        app.clone()
+            .oneshot(
+                Request::get(format!("/auth/google/callback?code=code&state={state}"))
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn callback_signs_in_with_issued_state() {
+        let app = app(StatusCode::OK).await;
+        let (state, cookie) = login(&app).await;
+
+        let response = callback(&app, &state, &cookie).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/auth/me");
+        let cookies = response
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|cookie| cookie.to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert!(cookies
+            .iter()
+            .any(|cookie| cookie.starts_with(&format!("{SESSION_COOKIE}="))));
+        // The session is encrypted, not readable by the client
+        assert!(!cookies
+            .iter()
+            .any(|cookie| cookie.contains("ada@example.com")));
+
+        let session = cookies
+            .iter()
+            .find(|cookie| cookie.starts_with(&format!("{SESSION_COOKIE}=")))
+            .and_then(|cookie| cookie.split(';').next())
+            .unwrap();
+        let (status, body) = me(&app, session).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user_id"], "google:1234");
+    }
+
+    async fn me(app: &Router, cookie: &str) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/auth/me")
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    // `Cookie` header carrying `session`, encrypted with `key`
+    fn session_cookie(key: &Key, session: &Session) -> String {
+        let response =
+            set_session(PrivateCookieJar::new(key.clone()), session, false).into_response();
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        cookie.split(';').next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn cookies_are_secure_behind_https() {
+        for (redirect_base_url, secure) in [
+            ("http://localhost:3000", false),
+            ("https://todos.example.com", true),
+        ] {
+            let google =
+                OAuthProvider::google("client-id".to_string(), "client-secret".to_string());
+            let state = OAuthState::new(vec![google], redirect_base_url).unwrap();
+            let key = state.key.clone();
+
+            let response = routes(state)
+                .oneshot(Request::get("/auth/google").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+            assert!(cookie.starts_with(&format!("{STATE_COOKIE}=")));
+            assert_eq!(cookie.contains("; Secure"), secure, "{cookie}");
+
+            let session = Session::new("google:1234".to_string(), Utc::now());
+            let response =
+                set_session(PrivateCookieJar::new(key), &session, secure).into_response();
+            let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+            assert_eq!(cookie.contains("; Secure"), secure, "{cookie}");
+        }
+    }
+
+    #[tokio::test]
+    async fn protected_routes_require_valid_session() {
+        let google = OAuthProvider::google("client-id".to_string(), "client-secret".to_string());
+        let state = OAuthState::new(vec![google], "http://localhost:3000").unwrap();
+        let key = state.key.clone();
+        let app = routes(state);
+
+        let cookie = session_cookie(
+            &key,
+            &Session::new("ada@example.com".to_string(), Utc::now()),
+        );
+        let (status, body) = me(&app, &cookie).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["user_id"], "ada@example.com");
+
+        let (status, body) = me(&app, "").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"]["code"], "unauthorized");
+
+        // Flipping a character breaks the encryption's authentication
+        let (name, value) = cookie.split_once('=').unwrap();
+        let last = if value.ends_with('A') { 'B' } else { 'A' };
+        let tampered = format!("{name}={}{last}", &value[..value.len() - 1]);
+        let (status, _) = me(&app, &tampered).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Encrypted with another key, e.g. before a restart
+        let cookie = session_cookie(
+            &Key::generate(),
+            &Session::new("ada@example.com".to_string(), Utc::now()),
+        );
+        let (status, _) = me(&app, &cookie).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let expired = Session {
+            user_id: "ada@example.com".to_string(),
+            expires_at: Utc::now() - ChronoDuration::minutes(1),
         };
+        let (status, _) = me(&app, &session_cookie(&key, &expired)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
 
-        let router = Router::new().route("/", get(hello_world));
+    #[tokio::test]
+    async fn sessions_expire_by_the_injected_clock() {
+        let start = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let clock = MockClock::new(start);
+        let state = oauth_state(StatusCode::OK)
+            .await
+            .with_clock(Arc::new(clock.clone()));
+        let key = state.key.clone();
+        let app = routes(state);
+
+        let session = Session::new("google:1234".to_string(), start.into());
+        let cookie = session_cookie(&key, &session);
+        let (status, body) = me(&app, &cookie).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["expires_at"], "2023-11-15T22:13:20Z");
+
+        // Just short of the TTL, then past it, without waiting a day
+        clock.advance(std::time::Duration::from_secs(
+            SESSION_TTL_HOURS as u64 * 3600 - 1,
+        ));
+        assert_eq!(me(&app, &cookie).await.0, StatusCode::OK);
+        clock.advance(std::time::Duration::from_secs(1));
+        assert_eq!(me(&app, &cookie).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn callback_rejects_mismatched_state() {
+        let app = app(StatusCode::OK).await;
+        let (_, cookie) = login(&app).await;
+
+        let response = callback(&app, "forged", &cookie).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Without the cookie no state was issued at all
+        let (state, _) = login(&app).await;
+        let response = callback(&app, &state, "").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    // `Cookie` header carrying `pending`, encrypted with `key`
+    fn pending_login_cookie(key: &Key, pending: &PendingLogin) -> String {
+        let response = PrivateCookieJar::new(key.clone())
+            .add(pending.to_cookie(false))
+            .into_response();
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        cookie.split(';').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn pkce_verifier_round_trips_through_pending_login() {
+        let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+        let pending = PendingLogin {
+            state: "state".to_string(),
+            verifier: Some(verifier.secret().clone()),
+        };
+
+        let key = Key::generate();
+        let mut headers = axum::http::HeaderMap::new();
+        let cookie = pending_login_cookie(&key, &pending);
+        headers.insert(header::COOKIE, cookie.parse().unwrap());
+        let jar = PrivateCookieJar::from_headers(&headers, key);
+
+        let restored = PendingLogin::from_jar(&jar).unwrap();
+        assert_eq!(restored.state, "state");
+        let restored = PkceCodeVerifier::new(restored.verifier.unwrap());
+        assert_eq!(
+            PkceCodeChallenge::from_code_verifier_sha256(&restored).as_str(),
+            challenge.as_str()
+        );
+    }
+
+    #[tokio::test]
+    async fn callback_rejects_missing_pkce_verifier() {
+        let state = oauth_state(StatusCode::OK).await;
+        let cookie = pending_login_cookie(
+            &state.key,
+            &PendingLogin {
+                state: "state".to_string(),
+                verifier: None,
+            },
+        );
+        let app = routes(state);
+
+        let response = callback(&app, "state", &cookie).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn unknown_providers_are_not_found() {
+        let app = app(StatusCode::OK).await;
+        // Known, but not configured
+        for uri in [
+            "/auth/github",
+            "/auth/github/callback?code=code&state=state",
+            "/auth/myspace",
+            "/auth/myspace/callback?code=code&state=state",
+        ] {
+            let response = app
+                .clone()
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{uri}");
+        }
+    }
+
+    #[test]
+    fn user_info_is_normalized_per_provider() {
+        let google = OAuthProvider::google(String::new(), String::new());
+        assert_eq!(
+            google.user_info(&json!({ "sub": "1234", "email": "ada@example.com", "name": "Ada" })),
+            Some(UserInfo {
+                id: "1234".to_string(),
+                email: Some("ada@example.com".to_string()),
+                name: Some("Ada".to_string()),
+            })
+        );
+
+        let github = OAuthProvider::github(String::new(), String::new());
+        assert_eq!(
+            github.user_info(&json!({ "id": 42, "login": "ada", "name": null, "email": null })),
+            Some(UserInfo {
+                id: "42".to_string(),
+                email: None,
+                name: Some("ada".to_string()),
+            })
+        );
+        assert_eq!(github.user_info(&json!({ "login": "ada" })), None);
+    }
 
-        // More info about this below - we will build an oauth client that can interface with any OAuth service
-        // Depending on the URLs we pass into it - read more here: https://docs.rs/oauth2/latest/oauth2/struct.Client.html?search=bassiclient#method.new
-        let client = build_oauth_client(oauth_id, oauth_secret);
+    #[tokio::test]
+    async fn callback_fails_when_token_exchange_fails() {
+        let app = app(StatusCode::BAD_REQUEST).await;
+        let (state, cookie) = login(&app).await;
 
-        Ok(router.into())
+        let response = callback(&app, &state, &cookie).await;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
     }
 }
@@ -0,0 +1,169 @@
+use crate::api::{AppState, StoreError, Todo};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Active and archived todos, as flushed
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Snapshot<T = Todo> {
+    pub(crate) todos: Vec<T>,
+    #[serde(default = "Vec::new")]
+    pub(crate) archived: Vec<T>,
+}
+
+impl<T> Default for Snapshot<T> {
+    fn default() -> Self {
+        Snapshot {
+            todos: Vec::new(),
+            archived: Vec::new(),
+        }
+    }
+}
+
+// Snapshots written before the archive was flushed are a bare list of active todos
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSnapshot {
+    Todos(Vec<Todo>),
+    Snapshot(Snapshot),
+}
+
+// Read todos flushed by a previous run; a missing file means a fresh start
+pub(crate) fn load(path: &Path) -> Result<Snapshot, StoreError> {
+    let contents = match fs::read(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Snapshot::default()),
+        Err(err) => return Err(backend(err)),
+    };
+    match serde_json::from_slice(&contents).map_err(backend)? {
+        StoredSnapshot::Todos(todos) => Ok(Snapshot {
+            todos,
+            archived: Vec::new(),
+        }),
+        StoredSnapshot::Snapshot(snapshot) => Ok(snapshot),
+    }
+}
+
+// Write a snapshot of the store, replacing the previous one atomically so a crash
+// mid-write leaves the last complete snapshot in place
+pub(crate) fn flush(state: &AppState, path: &Path) -> Result<(), StoreError> {
+    let contents = {
+        // Lock order is active store, then archive, everywhere
+        let todos = state.db.read()?;
+        let archive = state.archive.read()?;
+        let snapshot = Snapshot {
+            todos: todos.values().collect(),
+            archived: archive.values().collect(),
+        };
+        serde_json::to_vec(&snapshot).map_err(backend)?
+    };
+
+    let tmp_path = tmp_path(path);
+    let write = || -> io::Result<()> {
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(&contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    };
+    write().map_err(backend)
+}
+
+fn backend(err: impl std::fmt::Display) -> StoreError {
+    StoreError::Backend(err.to_string())
+}
+
+// Flush every `interval` until the process exits
+pub(crate) fn spawn_flusher(state: AppState, path: PathBuf, interval: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        // The first tick completes immediately; nothing has changed yet
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+            flush_and_log(state.clone(), path.clone()).await;
+        }
+    });
+}
+
+// `flush` off the async workers, logging rather than returning failures
+pub(crate) async fn flush_and_log(state: AppState, path: PathBuf) {
+    let flushed = tokio::task::spawn_blocking(move || flush(&state, &path)).await;
+    match flushed {
+        Ok(Ok(())) => tracing::debug!("flushed todos"),
+        Ok(Err(err)) => tracing::error!("failed to flush todos: {err}"),
+        Err(err) => tracing::error!("todo flush task failed: {err}"),
+    }
+}
+
+// Sibling of the snapshot, so the final rename stays on one filesystem
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn flushed_todos_are_restored() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", uuid::Uuid::new_v4()));
+
+        let state = AppState::new(Config::default());
+        let first = crate::api::insert_todo(&state, "first".to_string(), false).unwrap();
+        let second = crate::api::insert_todo(&state, "second".to_string(), false).unwrap();
+        let archived = crate::api::insert_todo(&state, "archived".to_string(), true).unwrap();
+        let archived =
+            crate::api::remove_todo(&state, &mut state.db.write().unwrap(), &archived.id).unwrap();
+        state
+            .archive
+            .write()
+            .unwrap()
+            .insert(archived.id, archived.clone());
+        flush(&state, &path).unwrap();
+        assert!(!tmp_path(&path).exists());
+
+        let restored = AppState::new(Config::default());
+        let snapshot = load(&path).unwrap();
+        restored.restore(snapshot.todos);
+        restored.restore_archived(snapshot.archived);
+        fs::remove_file(&path).unwrap();
+
+        let todos = restored.db.read().unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[&first.id], first);
+        assert_eq!(todos[&second.id], second);
+        assert_eq!(restored.counters.snapshot().total, 2);
+
+        let archive = restored.archive.read().unwrap();
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive[&archived.id], archived);
+    }
+
+    #[test]
+    fn snapshots_without_an_archive_are_restored() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", uuid::Uuid::new_v4()));
+
+        let state = AppState::new(Config::default());
+        let todo = crate::api::insert_todo(&state, "listed".to_string(), false).unwrap();
+        fs::write(&path, serde_json::to_vec(&[&todo]).unwrap()).unwrap();
+
+        let snapshot = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(snapshot.todos, [todo]);
+        assert!(snapshot.archived.is_empty());
+    }
+
+    #[test]
+    fn missing_file_loads_empty() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", uuid::Uuid::new_v4()));
+        let snapshot = load(&path).unwrap();
+        assert!(snapshot.todos.is_empty());
+        assert!(snapshot.archived.is_empty());
+    }
+}
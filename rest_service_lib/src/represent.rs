@@ -0,0 +1,196 @@
+use crate::api::{Todo, TodoSummary};
+use crate::config::{Config, FieldNaming, TimestampFormat};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+// How response bodies spell booleans, field names and timestamps, see
+// `LEGACY_BOOL_INTEGERS`, `JSON_FIELD_NAMING` and `TIMESTAMP_FORMAT`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Representation {
+    pub(crate) bool_integers: bool,
+    pub(crate) field_naming: FieldNaming,
+    pub(crate) timestamp_format: TimestampFormat,
+}
+
+impl From<&Config> for Representation {
+    fn from(config: &Config) -> Self {
+        Representation {
+            bool_integers: config.legacy_bool_integers,
+            field_naming: config.field_naming,
+            timestamp_format: config.timestamp_format,
+        }
+    }
+}
+
+impl Representation {
+    fn flag(&self, value: bool) -> Flag {
+        if self.bool_integers {
+            Flag::Integer(value)
+        } else {
+            Flag::Bool(value)
+        }
+    }
+
+    fn timestamp(&self, timestamp: DateTime<Utc>) -> Timestamp {
+        match self.timestamp_format {
+            TimestampFormat::Rfc3339 => Timestamp::Rfc3339(timestamp),
+            TimestampFormat::EpochMillis => Timestamp::EpochMillis(timestamp),
+        }
+    }
+
+    // `body` under the field names of `field_naming`
+    fn named<T, C: From<T>>(&self, body: T) -> Named<T, C> {
+        match self.field_naming {
+            FieldNaming::SnakeCase => Named::SnakeCase(body),
+            FieldNaming::CamelCase => Named::CamelCase(C::from(body)),
+        }
+    }
+}
+
+// Values sent in a response body shaped by a `Representation`, e.g.
+// `Json(todo.represent(representation))`
+pub(crate) trait Represent {
+    type Body: Serialize;
+
+    fn represent(&self, representation: Representation) -> Self::Body;
+}
+
+impl<T: Represent> Represent for Vec<T> {
+    type Body = Vec<T::Body>;
+
+    fn represent(&self, representation: Representation) -> Self::Body {
+        self.iter()
+            .map(|value| value.represent(representation))
+            .collect()
+    }
+}
+
+// `true`/`false` as `1`/`0`
+mod bool_integer {
+    use serde::Serializer;
+
+    pub(super) fn serialize<S: Serializer>(value: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8((*value).into())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Flag {
+    Bool(bool),
+    Integer(#[serde(with = "bool_integer")] bool),
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Timestamp {
+    Rfc3339(DateTime<Utc>),
+    EpochMillis(#[serde(with = "chrono::serde::ts_milliseconds")] DateTime<Utc>),
+}
+
+// A body with either its own field names or their camelCase spelling
+#[derive(Serialize)]
+#[serde(untagged)]
+pub(crate) enum Named<T, C> {
+    SnakeCase(T),
+    CamelCase(C),
+}
+
+#[derive(Serialize)]
+pub(crate) struct TodoBody {
+    id: Uuid,
+    text: String,
+    completed: Flag,
+    completed_at: Option<Timestamp>,
+    created_at: Timestamp,
+    updated_at: Timestamp,
+    position: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CamelCaseTodoBody {
+    id: Uuid,
+    text: String,
+    completed: Flag,
+    completed_at: Option<Timestamp>,
+    created_at: Timestamp,
+    updated_at: Timestamp,
+    position: u64,
+}
+
+impl From<TodoBody> for CamelCaseTodoBody {
+    fn from(body: TodoBody) -> Self {
+        CamelCaseTodoBody {
+            id: body.id,
+            text: body.text,
+            completed: body.completed,
+            completed_at: body.completed_at,
+            created_at: body.created_at,
+            updated_at: body.updated_at,
+            position: body.position,
+        }
+    }
+}
+
+impl Represent for Todo {
+    type Body = Named<TodoBody, CamelCaseTodoBody>;
+
+    fn represent(&self, representation: Representation) -> Self::Body {
+        representation.named(TodoBody {
+            id: self.id,
+            text: self.text.clone(),
+            completed: representation.flag(self.completed),
+            completed_at: self
+                .completed_at
+                .map(|timestamp| representation.timestamp(timestamp)),
+            created_at: representation.timestamp(self.created_at),
+            updated_at: representation.timestamp(self.updated_at),
+            position: self.position,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct TodoSummaryBody {
+    id: Uuid,
+    completed: Flag,
+    completed_at: Option<Timestamp>,
+    updated_at: Timestamp,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CamelCaseTodoSummaryBody {
+    id: Uuid,
+    completed: Flag,
+    completed_at: Option<Timestamp>,
+    updated_at: Timestamp,
+}
+
+impl From<TodoSummaryBody> for CamelCaseTodoSummaryBody {
+    fn from(body: TodoSummaryBody) -> Self {
+        CamelCaseTodoSummaryBody {
+            id: body.id,
+            completed: body.completed,
+            completed_at: body.completed_at,
+            updated_at: body.updated_at,
+        }
+    }
+}
+
+impl Represent for TodoSummary {
+    type Body = Named<TodoSummaryBody, CamelCaseTodoSummaryBody>;
+
+    fn represent(&self, representation: Representation) -> Self::Body {
+        representation.named(TodoSummaryBody {
+            id: self.id,
+            completed: representation.flag(self.completed),
+            completed_at: self
+                .completed_at
+                .map(|timestamp| representation.timestamp(timestamp)),
+            updated_at: representation.timestamp(self.updated_at),
+        })
+    }
+}
@@ -0,0 +1,255 @@
+use crate::api::{self, AppState, StoreError, UpdateTodo};
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+const JSONRPC_VERSION: &str = "2.0";
+
+// Error codes defined by the JSON-RPC 2.0 specification
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Implementation defined: the todo operation failed, `data.status` holds the REST status
+const TODO_ERROR: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    // Absent for notifications, which get no response
+    id: Option<Value>,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+    id: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<StoreError> for RpcError {
+    fn from(error: StoreError) -> Self {
+        let message = error.to_string();
+        let status = api::ApiError::from(error).status();
+        RpcError {
+            code: TODO_ERROR,
+            message,
+            data: Some(json!({ "status": status.as_u16() })),
+        }
+    }
+}
+
+impl RpcResponse {
+    fn result(id: Value, result: Value) -> Self {
+        RpcResponse {
+            jsonrpc: JSONRPC_VERSION,
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+
+    fn error(id: Value, error: RpcError) -> Self {
+        RpcResponse {
+            jsonrpc: JSONRPC_VERSION,
+            result: None,
+            error: Some(error),
+            id,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ListParams {
+    offset: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdParams {
+    id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+struct CreateParams {
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateParams {
+    id: Uuid,
+    text: Option<String>,
+    completed: Option<bool>,
+}
+
+/// JSON-RPC 2.0 endpoint
+///
+/// Dispatch a single call or a batch of calls to the todo operations:
+/// `todos.list`, `todos.get`, `todos.create`, `todos.update` and `todos.delete`
+pub(crate) async fn rpc_handler(State(state): State<AppState>, body: Bytes) -> Response {
+    let Ok(payload) = serde_json::from_slice::<Value>(&body) else {
+        let error = RpcError::new(PARSE_ERROR, "Parse error");
+        return Json(RpcResponse::error(Value::Null, error)).into_response();
+    };
+
+    match payload {
+        Value::Array(calls) if calls.is_empty() => {
+            let error = RpcError::new(INVALID_REQUEST, "Invalid Request");
+            Json(RpcResponse::error(Value::Null, error)).into_response()
+        }
+        Value::Array(calls)
+            if state
+                .config
+                .max_batch_size
+                .is_some_and(|max| calls.len() > max) =>
+        {
+            // Refused as a whole before running any call
+            let error = RpcError::new(INVALID_REQUEST, "Batch too large");
+            (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(RpcResponse::error(Value::Null, error)),
+            )
+                .into_response()
+        }
+        Value::Array(calls) => {
+            let responses = calls
+                .into_iter()
+                .filter_map(|call| call_one(&state, call))
+                .collect::<Vec<_>>();
+            if responses.is_empty() {
+                StatusCode::NO_CONTENT.into_response()
+            } else {
+                Json(responses).into_response()
+            }
+        }
+        call => match call_one(&state, call) {
+            Some(response) => Json(response).into_response(),
+            None => StatusCode::NO_CONTENT.into_response(),
+        },
+    }
+}
+
+// Run one call, returning `None` for notifications
+fn call_one(state: &AppState, call: Value) -> Option<RpcResponse> {
+    // Echoed back on an invalid request when it could be read
+    let id = call
+        .get("id")
+        .filter(|id| id.is_string() || id.is_number())
+        .cloned()
+        .unwrap_or(Value::Null);
+    let request = match serde_json::from_value::<RpcRequest>(call) {
+        Ok(request) if request.jsonrpc == JSONRPC_VERSION => request,
+        _ => {
+            let error = RpcError::new(INVALID_REQUEST, "Invalid Request");
+            return Some(RpcResponse::error(id, error));
+        }
+    };
+
+    let result = dispatch(state, &request.method, request.params);
+    let id = request.id?;
+
+    Some(match result {
+        Ok(result) => RpcResponse::result(id, result),
+        Err(error) => RpcResponse::error(id, error),
+    })
+}
+
+fn dispatch(state: &AppState, method: &str, params: Value) -> Result<Value, RpcError> {
+    match method {
+        "todos.list" => {
+            let params = if params.is_null() {
+                ListParams::default()
+            } else {
+                parse_params::<ListParams>(params)?
+            };
+            // Sorted and paged the way `GET /todos` is
+            let pagination = api::Pagination {
+                offset: params.offset,
+                limit: params.limit,
+                ..api::Pagination::default()
+            }
+            .resolve()
+            .map_err(invalid_params)?;
+            let todos = state.db.read().map_err(StoreError::from)?;
+            let matches = api::matching_todos(&todos, &pagination).map_err(invalid_params)?;
+            let (_, todos) = api::page_todos(matches, &pagination);
+            to_result(&todos)
+        }
+        "todos.get" => {
+            let IdParams { id } = parse_params(params)?;
+            let todos = state.db.read().map_err(StoreError::from)?;
+            let todo = todos.get(&id).ok_or(StoreError::NotFound)?;
+            state.access_log.touch(id);
+            to_result(todo)
+        }
+        "todos.create" => {
+            let CreateParams { text } = parse_params(params)?;
+            let todo = api::insert_todo(state, text, false)?;
+            to_result(&todo)
+        }
+        "todos.update" => {
+            let UpdateParams {
+                id,
+                text,
+                completed,
+            } = parse_params(params)?;
+            let mut todos = state.db.write().map_err(StoreError::from)?;
+            api::flush_pending_update(state, &mut todos, &id);
+            let todo = todos.get_mut(&id).ok_or(StoreError::NotFound)?;
+            api::apply_update(state, todo, UpdateTodo { text, completed })?;
+            state.access_log.touch(id);
+            to_result(&*todo)
+        }
+        "todos.delete" => {
+            let IdParams { id } = parse_params(params)?;
+            let mut todos = state.db.write().map_err(StoreError::from)?;
+            api::flush_pending_update(state, &mut todos, &id);
+            api::remove_todo(state, &mut todos, &id).ok_or(StoreError::NotFound)?;
+            Ok(Value::Null)
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, "Method not found")),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(invalid_params)
+}
+
+fn invalid_params(error: impl std::fmt::Display) -> RpcError {
+    RpcError::new(INVALID_PARAMS, format!("Invalid params: {error}"))
+}
+
+fn to_result<T: Serialize + ?Sized>(value: &T) -> Result<Value, RpcError> {
+    Ok(serde_json::to_value(value).expect("todos serialize to JSON"))
+}